@@ -1,4 +1,6 @@
+use std::fmt::Display;
 use std::str::FromStr;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 #[derive(Debug, thiserror::Error)]
@@ -39,6 +41,15 @@ pub struct Report {
     pub claimant: Option<u64>,
     #[serde(default)]
     pub location: Location,
+    /// When it was first claimed
+    #[serde(default)]
+    pub claimed_at: Option<String>,
+    /// When it was closed
+    #[serde(default)]
+    pub closed_at: Option<String>,
+    /// The guild it belongs to
+    #[serde(default)]
+    pub guild_id: Option<u64>,
 }
 /// Various status of reports.
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -116,11 +127,213 @@ pub struct Action {
     pub server: Location,
     pub claimant: u64,
     pub report: Option<u32>,
+    /// What they got. None if it wasn't one of these, or nobody could tell from `action`
+    #[serde(default)]
+    pub punishment: Option<Punishment>,
+    /// When the audit was made. None for audits from before this was tracked
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// The guild it belongs to
+    #[serde(default)]
+    pub guild_id: Option<u64>,
+}
+
+/// What an audit actually did to someone.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Punishment {
+    Warn,
+    Kick,
+    Mute,
+    Timeout(Duration),
+    Ban(Duration),
+    PermanentBan,
+}
+
+impl Punishment {
+    /// every kind [`Punishment::to_db`] gives, most severe last
+    pub const KINDS: [&'static str; 6] =
+        ["warn", "kick", "mute", "timeout", "ban", "permanent_ban"];
+
+    /// the kind (for the database), and how many seconds it's for if it has a length
+    pub fn to_db(&self) -> (&'static str, Option<i64>) {
+        match self {
+            Self::Warn => ("warn", None),
+            Self::Kick => ("kick", None),
+            Self::Mute => ("mute", None),
+            Self::Timeout(d) => ("timeout", Some(d.as_secs() as i64)),
+            Self::Ban(d) => ("ban", Some(d.as_secs() as i64)),
+            Self::PermanentBan => ("permanent_ban", None),
+        }
+    }
+    /// the other way around. None for kinds it doesn't know, or a timeout/ban without a length
+    pub fn from_db(kind: &str, secs: Option<i64>) -> Option<Self> {
+        let length = || Some(Duration::from_secs(u64::try_from(secs?).ok()?));
+        match kind {
+            "warn" => Some(Self::Warn),
+            "kick" => Some(Self::Kick),
+            "mute" => Some(Self::Mute),
+            "timeout" => length().map(Self::Timeout),
+            "ban" => length().map(Self::Ban),
+            "permanent_ban" => Some(Self::PermanentBan),
+            _ => None,
+        }
+    }
+    /// a kind from [`Punishment::to_db`], for people
+    pub fn kind_name(kind: &str) -> &'static str {
+        match kind {
+            "warn" => "Warn",
+            "kick" => "Kick",
+            "mute" => "Mute",
+            "timeout" => "Timeout",
+            "ban" => "Ban",
+            "permanent_ban" => "Permanent ban",
+            _ => "Other",
+        }
+    }
+    /// how long it lasts. None for things that don't, or last forever
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            Self::Timeout(d) | Self::Ban(d) => Some(*d),
+            _ => None,
+        }
+    }
+    /// best guess at what free text like "ban 1d", "1 day ban" or "banned for a day" means.
+    /// if it says more than one thing, the most severe wins. None if it can't tell, including
+    /// bans and timeouts that don't say how long
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.to_lowercase();
+        let words: Vec<&str> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let has = |any: &[&str]| words.iter().any(|w| any.contains(w));
+        let banned = has(&["ban", "bans", "banned", "banning"]);
+        if has(&["permaban", "permabanned", "permban", "permbanned"])
+            || (banned
+                && has(&[
+                    "perm",
+                    "perma",
+                    "permanent",
+                    "permanently",
+                    "forever",
+                    "indefinite",
+                    "indefinitely",
+                ]))
+        {
+            return Some(Self::PermanentBan);
+        }
+        let length = parse_duration(&text);
+        if banned {
+            // SL calls anything longer than 50 years permanent
+            return match length? {
+                d if d.as_secs() >= 50 * YEAR => Some(Self::PermanentBan),
+                d if d.is_zero() => Some(Self::Kick),
+                d => Some(Self::Ban(d)),
+            };
+        }
+        if has(&["timeout", "timeouts", "timedout"]) || (has(&["timed"]) && has(&["out"])) {
+            return length.map(Self::Timeout);
+        }
+        if has(&["kick", "kicks", "kicked"]) {
+            Some(Self::Kick)
+        } else if has(&["mute", "mutes", "muted"]) {
+            Some(Self::Mute)
+        } else if has(&["warn", "warns", "warned", "warning", "warnings"]) {
+            Some(Self::Warn)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Punishment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(d) => write!(f, "Timeout ({})", format_length(*d)),
+            Self::Ban(d) => write!(f, "Ban ({})", format_length(*d)),
+            p => f.write_str(Self::kind_name(p.to_db().0)),
+        }
+    }
+}
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+const YEAR: u64 = 365 * DAY;
+
+/// add up every `<number> <unit>` in `text`, like `1d 12h`, `2 weeks` or `a day`.
+/// None if there aren't any
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    // split `1d12h` into `1 d 12 h`
+    let mut tokens: Vec<String> = vec![];
+    let mut last: Option<bool> = None;
+    for c in text.to_lowercase().chars() {
+        if !c.is_alphanumeric() {
+            last = None;
+            continue;
+        }
+        let digit = c.is_ascii_digit();
+        match tokens.last_mut() {
+            Some(t) if last == Some(digit) => t.push(c),
+            _ => tokens.push(c.to_string()),
+        }
+        last = Some(digit);
+    }
+    let mut total = None;
+    for pair in tokens.windows(2) {
+        let count = match pair[0].as_str() {
+            "a" | "an" | "one" => 1,
+            n => match n.parse::<u64>() {
+                Ok(n) => n,
+                Err(_) => continue,
+            },
+        };
+        let unit = match pair[1].as_str() {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => MINUTE,
+            "h" | "hr" | "hrs" | "hour" | "hours" => HOUR,
+            "d" | "day" | "days" => DAY,
+            "w" | "wk" | "wks" | "week" | "weeks" => WEEK,
+            "mo" | "month" | "months" => 30 * DAY,
+            "y" | "yr" | "yrs" | "year" | "years" => YEAR,
+            _ => continue,
+        };
+        total = Some(
+            total
+                .unwrap_or(0u64)
+                .saturating_add(count.saturating_mul(unit)),
+        );
+    }
+    total.map(Duration::from_secs)
+}
+
+/// like `1d 12h` or `30m`, the biggest two units that aren't 0
+pub fn format_length(d: Duration) -> String {
+    let secs = d.as_secs();
+    let parts: Vec<String> = [(DAY, "d"), (HOUR, "h"), (MINUTE, "m"), (1, "s")]
+        .into_iter()
+        .scan(secs, |left, (size, unit)| {
+            let n = *left / size;
+            *left %= size;
+            Some((n, unit))
+        })
+        .filter(|(n, _)| *n > 0)
+        .take(2)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect();
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ReportStatus;
+    use std::time::Duration;
+
+    use crate::{Punishment, ReportStatus};
 
     #[test]
     fn test_report_status_from_db() {
@@ -143,4 +356,60 @@ mod tests {
         assert_eq!(&ReportStatus::Expired.to_db(), "expired");
         assert_eq!(&ReportStatus::Claimed.to_db(), "claimed");
     }
+    #[test]
+    fn test_punishment_parse() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        for text in [
+            "ban 1d",
+            "1 day ban",
+            "banned for a day",
+            "Banned for 1d 0h (in game)",
+        ] {
+            assert_eq!(
+                Punishment::parse(text),
+                Some(Punishment::Ban(day)),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(Punishment::parse("ban 2w"), Some(Punishment::Ban(day * 14)));
+        assert_eq!(
+            Punishment::parse("perm ban"),
+            Some(Punishment::PermanentBan)
+        );
+        assert_eq!(
+            Punishment::parse("Banned permanently"),
+            Some(Punishment::PermanentBan)
+        );
+        assert_eq!(
+            Punishment::parse("timed out for 1h30m"),
+            Some(Punishment::Timeout(Duration::from_secs(90 * 60)))
+        );
+        assert_eq!(
+            Punishment::parse("warned, then kicked"),
+            Some(Punishment::Kick)
+        );
+        assert_eq!(Punishment::parse("verbal warning"), Some(Punishment::Warn));
+        assert_eq!(Punishment::parse("muted"), Some(Punishment::Mute));
+        // how long?
+        assert_eq!(Punishment::parse("ban"), None);
+        assert_eq!(Punishment::parse("talked to them"), None);
+    }
+    #[test]
+    fn test_punishment_db_round_trip() {
+        for p in [
+            Punishment::Warn,
+            Punishment::Timeout(Duration::from_secs(600)),
+            Punishment::Ban(Duration::from_secs(86400)),
+            Punishment::PermanentBan,
+        ] {
+            let (kind, secs) = p.to_db();
+            assert_eq!(Punishment::from_db(kind, secs), Some(p));
+        }
+        assert_eq!(Punishment::from_db("ban", None), None);
+        assert_eq!(
+            Punishment::Ban(Duration::from_secs(129600)).to_string(),
+            "Ban (1d 12h)"
+        );
+    }
 }
@@ -0,0 +1,9 @@
+-- Add down migration script here
+drop trigger report_search_ai;
+drop trigger report_search_ad;
+drop trigger report_search_au;
+drop table ReportSearch;
+drop trigger actions_search_ai;
+drop trigger actions_search_ad;
+drop trigger actions_search_au;
+drop table ActionsSearch;
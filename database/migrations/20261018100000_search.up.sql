@@ -0,0 +1,29 @@
+-- Add up migration script here
+create virtual table ReportSearch using fts5(report_reason, reporter_name, reported_name, tokenize = 'porter unicode61', content = Reports, content_rowid = id);
+create virtual table ActionsSearch using fts5(offense, action, tokenize = 'porter unicode61', content = Actions, content_rowid = id);
+
+CREATE TRIGGER report_search_ai AFTER INSERT ON Reports BEGIN
+    INSERT INTO ReportSearch(rowid, report_reason, reporter_name, reported_name) VALUES (new.id, new.report_reason, new.reporter_name, new.reported_name);
+END;
+CREATE TRIGGER report_search_ad AFTER DELETE ON Reports BEGIN
+    INSERT INTO ReportSearch(ReportSearch, rowid, report_reason, reporter_name, reported_name) VALUES ('delete', old.id, old.report_reason, old.reporter_name, old.reported_name);
+END;
+CREATE TRIGGER report_search_au AFTER UPDATE OF report_reason, reporter_name, reported_name ON Reports BEGIN
+    INSERT INTO ReportSearch(ReportSearch, rowid, report_reason, reporter_name, reported_name) VALUES ('delete', old.id, old.report_reason, old.reporter_name, old.reported_name);
+    INSERT INTO ReportSearch(rowid, report_reason, reporter_name, reported_name) VALUES (new.id, new.report_reason, new.reporter_name, new.reported_name);
+END;
+
+CREATE TRIGGER actions_search_ai AFTER INSERT ON Actions BEGIN
+    INSERT INTO ActionsSearch(rowid, offense, action) VALUES (new.id, new.offense, new.action);
+END;
+CREATE TRIGGER actions_search_ad AFTER DELETE ON Actions BEGIN
+    INSERT INTO ActionsSearch(ActionsSearch, rowid, offense, action) VALUES ('delete', old.id, old.offense, old.action);
+END;
+CREATE TRIGGER actions_search_au AFTER UPDATE OF offense, action ON Actions BEGIN
+    INSERT INTO ActionsSearch(ActionsSearch, rowid, offense, action) VALUES ('delete', old.id, old.offense, old.action);
+    INSERT INTO ActionsSearch(rowid, offense, action) VALUES (new.id, new.offense, new.action);
+END;
+
+-- index everything that came in while search was gone
+insert into ReportSearch(ReportSearch) values ('rebuild');
+insert into ActionsSearch(ActionsSearch) values ('rebuild');
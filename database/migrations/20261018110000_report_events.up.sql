@@ -0,0 +1,19 @@
+-- Add up migration script here
+create table if not exists ReportEvents (
+    id integer primary key not null,
+    report_id integer not null,
+    -- null when lurk-chan did it (expiry, new report)
+    actor text,
+    time text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    -- null when the report was created
+    old_status text,
+    new_status text not null,
+    note text,
+    foreign key(report_id) references Reports(id)
+);
+
+create index ReportEvents_report_id on ReportEvents(report_id);
+
+-- we only know the current state of older reports
+insert into ReportEvents(report_id, actor, time, old_status, new_status, note)
+    select id, null, time, null, report_status, 'Imported from before events were tracked' from Reports;
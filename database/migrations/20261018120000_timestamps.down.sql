@@ -0,0 +1,4 @@
+-- Add down migration script here
+alter table Actions drop column created_at;
+alter table Reports drop column claimed_at;
+alter table Reports drop column closed_at;
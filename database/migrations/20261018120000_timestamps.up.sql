@@ -0,0 +1,4 @@
+-- Add up migration script here
+alter table Actions add column created_at text;
+alter table Reports add column claimed_at text;
+alter table Reports add column closed_at text;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- discord side effects that still have to happen after a database change
+create table if not exists Outbox (
+    id integer primary key not null,
+    -- post_audit, update_report, update_audit or delete_message
+    kind text not null,
+    -- the report or audit it's about
+    target integer,
+    -- for delete_message
+    channel text,
+    message text,
+    attempts integer not null default 0,
+    last_error text,
+    created_at text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    next_attempt text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+create index Outbox_next_attempt on Outbox(next_attempt);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table ReportPosts;
+drop index Reports_source_message;
+alter table Reports drop column source_message;
+alter table Reports drop column source_channel;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- the webhook message a report came from, so it's never turned into a report twice
+alter table Reports add column source_channel text;
+alter table Reports add column source_message text;
+
+create unique index Reports_source_message on Reports(source_message);
+
+-- who's posting a report's message. whoever turned it into a report has it, and anyone else only
+-- takes over once it's old enough that whoever had it must have died
+create table if not exists ReportPosts (
+    report_id integer primary key references Reports(id) on delete cascade,
+    claimed_at text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
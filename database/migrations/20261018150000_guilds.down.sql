@@ -0,0 +1,6 @@
+-- Add down migration script here
+drop index Actions_guild_id;
+drop index Reports_guild_id;
+alter table Actions drop column guild_id;
+alter table Reports drop column guild_id;
+drop table GuildSettings;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- where each guild wants things posted. set with /setup
+create table if not exists GuildSettings (
+    guild_id text primary key not null,
+    sl_audit text,
+    discord_audit text,
+    discord_reports text,
+    stats text
+);
+
+-- null for everything from before guilds were tracked, lurk-chan hands those to the guild from the config on startup
+alter table Reports add column guild_id text;
+alter table Actions add column guild_id text;
+
+create index Reports_guild_id on Reports(guild_id);
+create index Actions_guild_id on Actions(guild_id);
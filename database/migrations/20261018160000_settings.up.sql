@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- everything /config can change, per guild. channels live in GuildSettings
+create table if not exists Settings (
+    guild_id text not null,
+    key text not null,
+    value text not null,
+    updated_by text,
+    updated_at text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    primary key (guild_id, key)
+);
@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- reports the expiry task can't make sense of. they're left alone until someone fixes them
+create table if not exists Quarantine (
+    report_id integer primary key references Reports(id) on delete cascade,
+    reason text not null,
+    quarantined_at text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+-- reports staff were warned about before they expire. `since` is when the report last changed,
+-- so a report that was unclaimed or reopened gets warned about again
+create table if not exists ExpiryWarnings (
+    report_id integer primary key references Reports(id) on delete cascade,
+    since text not null
+);
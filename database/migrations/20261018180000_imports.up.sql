@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- reports and audits that were imported instead of made in lurk-chan, and where from.
+-- `key` identifies the row in what it was imported from, so importing it again does nothing
+create table if not exists Imports (
+    key text primary key not null,
+    -- 'report' or 'audit'
+    kind text not null,
+    row_id integer not null,
+    source text not null,
+    imported_at text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+create index if not exists Imports_row on Imports(kind, row_id);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+drop index Actions_punishment;
+alter table Actions drop column punishment_secs;
+alter table Actions drop column punishment;
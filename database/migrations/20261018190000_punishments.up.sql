@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- what an audit did to someone, as `common::Punishment::to_db` writes it, and how long for.
+-- null is an audit from before this that hasn't been looked at yet, they're classified from their
+-- action text (as best we can) right after migrating. 'other' is one that isn't any of them
+alter table Actions add column punishment text;
+alter table Actions add column punishment_secs integer;
+create index if not exists Actions_punishment on Actions(punishment);
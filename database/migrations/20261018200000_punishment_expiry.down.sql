@@ -0,0 +1,7 @@
+-- Add down migration script here
+drop trigger Actions_expires_update;
+drop trigger Actions_expires_insert;
+drop table NoticeMessages;
+drop table PunishmentNotices;
+drop index Actions_expires_at;
+alter table Actions drop column expires_at;
@@ -0,0 +1,39 @@
+-- Add up migration script here
+-- when an audit's punishment runs out: when it was made (or its report came in, for old audits)
+-- plus how long it's for. null for punishments that don't run out. kept up to date by the triggers
+alter table Actions add column expires_at text;
+update Actions set expires_at = strftime('%Y-%m-%dT%H:%M:%SZ',
+    coalesce(created_at, (select time from Reports R where R.id = Actions.report)),
+    '+' || punishment_secs || ' seconds')
+where punishment_secs is not null;
+create index if not exists Actions_expires_at on Actions(expires_at) where expires_at is not null;
+
+-- punishments that were announced as over. changing how long one is for announces it again
+create table if not exists PunishmentNotices (
+    action_id integer primary key references Actions(id) on delete cascade,
+    noticed_at text not null default (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+-- the messages saying a punishment ran out, so they aren't mistaken for strays. kept when the
+-- punishment changes and gets announced again
+create table if not exists NoticeMessages (
+    action_id integer not null references Actions(id) on delete cascade,
+    message text unique not null,
+    channel text not null
+);
+
+create trigger if not exists Actions_expires_insert after insert on Actions
+begin
+    update Actions set expires_at = strftime('%Y-%m-%dT%H:%M:%SZ',
+        coalesce(new.created_at, (select time from Reports R where R.id = new.report)),
+        '+' || new.punishment_secs || ' seconds')
+    where id = new.id;
+end;
+create trigger if not exists Actions_expires_update after update of punishment_secs, created_at on Actions
+when old.punishment_secs is not new.punishment_secs or old.created_at is not new.created_at
+begin
+    update Actions set expires_at = strftime('%Y-%m-%dT%H:%M:%SZ',
+        coalesce(new.created_at, (select time from Reports R where R.id = new.report)),
+        '+' || new.punishment_secs || ' seconds')
+    where id = new.id;
+    delete from PunishmentNotices where action_id = new.id;
+end;
@@ -0,0 +1,304 @@
+use std::io::Write;
+
+use common::{Location, ReportStatus};
+use futures::{Stream, TryStreamExt as _};
+use serde::Serialize;
+
+use crate::{Database, Error};
+
+/// what [`Database::export`] can write out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportTable {
+    Reports,
+    Audits,
+    /// every edit made to an audit, with the audit before and after
+    Edits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// a header, then a row per line
+    Csv,
+    /// one JSON object per line
+    Jsonl,
+}
+
+impl ExportFormat {
+    /// what to end the file name with
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// which rows to export. None matches everything
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub guild: Option<u64>,
+    /// only rows from this time on (RFC 3339)
+    pub since: Option<String>,
+    /// only rows from before this time (RFC 3339)
+    pub until: Option<String>,
+    pub location: Option<Location>,
+    /// reports with this status. audits and edits don't have one, so it's ignored for them
+    pub status: Option<ReportStatus>,
+    /// who claimed the report or made the audit. for edits, who made the edit
+    pub claimant: Option<u64>,
+    /// the ID of who the report or audit is about
+    pub target: Option<String>,
+}
+
+/// a row as it is in the database, so spreadsheets get exactly what's stored
+trait Row: Serialize {
+    /// the fields, in the order they go in a CSV
+    const COLUMNS: &'static [&'static str];
+}
+
+#[derive(Serialize)]
+struct ReportRow {
+    id: i64,
+    reporter_id: String,
+    reporter_name: String,
+    reported_id: String,
+    reported_name: String,
+    report_reason: String,
+    report_status: String,
+    server: String,
+    time: String,
+    claimant: Option<String>,
+    location: String,
+    claimed_at: Option<String>,
+    closed_at: Option<String>,
+    guild_id: Option<String>,
+}
+
+impl Row for ReportRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "reporter_id",
+        "reporter_name",
+        "reported_id",
+        "reported_name",
+        "report_reason",
+        "report_status",
+        "server",
+        "time",
+        "claimant",
+        "location",
+        "claimed_at",
+        "closed_at",
+        "guild_id",
+    ];
+}
+
+#[derive(Serialize)]
+struct AuditRow {
+    id: i64,
+    target_id: String,
+    target_username: String,
+    offense: String,
+    action: String,
+    server: String,
+    claimant: String,
+    report: Option<i64>,
+    created_at: Option<String>,
+    guild_id: Option<String>,
+    punishment: Option<String>,
+    punishment_secs: Option<i64>,
+}
+
+impl Row for AuditRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "target_id",
+        "target_username",
+        "offense",
+        "action",
+        "server",
+        "claimant",
+        "report",
+        "created_at",
+        "guild_id",
+        "punishment",
+        "punishment_secs",
+    ];
+}
+
+#[derive(Serialize)]
+struct EditRow {
+    action_id: i64,
+    who: String,
+    time: String,
+    changes: String,
+    old: String,
+    new: String,
+}
+
+impl Row for EditRow {
+    const COLUMNS: &'static [&'static str] = &["action_id", "who", "time", "changes", "old", "new"];
+}
+
+impl Database {
+    /// write every row of `table` that matches `filter` to `out`, oldest first, a row at a time.
+    /// returns how many rows there were
+    pub async fn export(
+        &self,
+        table: ExportTable,
+        format: ExportFormat,
+        filter: &ExportFilter,
+        out: &mut impl Write,
+    ) -> Result<usize, Error> {
+        let guild = filter.guild.map(|g| g.to_string());
+        let since = filter.since.as_deref();
+        let until = filter.until.as_deref();
+        let location = filter.location.as_ref().map(ToString::to_string);
+        let status = filter.status.as_ref().map(ReportStatus::to_db);
+        let claimant = filter.claimant.map(|c| c.to_string());
+        let target = filter.target.as_deref();
+        match table {
+            ExportTable::Reports => {
+                let rows = sqlx::query_as!(
+                    ReportRow,
+                    r#"select id as "id!", reporter_id, reporter_name, reported_id, reported_name, report_reason, report_status,
+                    server, time, claimant, location, claimed_at, closed_at, guild_id
+                    from Reports
+                    where (?1 is null or guild_id = ?1)
+                    and (?2 is null or julianday(time) >= julianday(?2))
+                    and (?3 is null or julianday(time) < julianday(?3))
+                    and (?4 is null or location = ?4)
+                    and (?5 is null or report_status = ?5)
+                    and (?6 is null or claimant = ?6)
+                    and (?7 is null or reported_id = ?7)
+                    order by id"#,
+                    guild,
+                    since,
+                    until,
+                    location,
+                    status,
+                    claimant,
+                    target
+                )
+                .fetch(&self.pool);
+                write_rows(rows, format, out).await
+            }
+            // audits from before creation times were tracked go by their report, like the leaderboard
+            ExportTable::Audits => {
+                let rows = sqlx::query_as!(
+                    AuditRow,
+                    r#"select id, target_id, target_username, offense, action, server, claimant, report, created_at, guild_id,
+                    punishment, punishment_secs
+                    from Actions A
+                    where (?1 is null or A.guild_id = ?1)
+                    and (?2 is null or julianday(coalesce(A.created_at, (select time from Reports R where R.id = A.report))) >= julianday(?2))
+                    and (?3 is null or julianday(coalesce(A.created_at, (select time from Reports R where R.id = A.report))) < julianday(?3))
+                    and (?4 is null or A.server = ?4)
+                    and (?5 is null or A.claimant = ?5)
+                    and (?6 is null or A.target_id = ?6)
+                    order by id"#,
+                    guild,
+                    since,
+                    until,
+                    location,
+                    claimant,
+                    target
+                )
+                .fetch(&self.pool);
+                write_rows(rows, format, out).await
+            }
+            ExportTable::Edits => {
+                let rows = sqlx::query_as!(
+                    EditRow,
+                    r#"select E.action_id, E.who, E.time, E.changes, E.old, E.new
+                    from AuditEdits E join Actions A on A.id = E.action_id
+                    where (?1 is null or A.guild_id = ?1)
+                    and (?2 is null or julianday(E.time) >= julianday(?2))
+                    and (?3 is null or julianday(E.time) < julianday(?3))
+                    and (?4 is null or A.server = ?4)
+                    and (?5 is null or E.who = ?5)
+                    and (?6 is null or A.target_id = ?6)
+                    order by E.rowid"#,
+                    guild,
+                    since,
+                    until,
+                    location,
+                    claimant,
+                    target
+                )
+                .fetch(&self.pool);
+                write_rows(rows, format, out).await
+            }
+        }
+    }
+}
+
+async fn write_rows<R: Row>(
+    mut rows: impl Stream<Item = Result<R, sqlx::Error>> + Unpin,
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> Result<usize, Error> {
+    if format == ExportFormat::Csv {
+        write_csv_line(out, R::COLUMNS.iter().map(|c| c.to_string()))?;
+    }
+    let mut count = 0;
+    while let Some(row) = rows.try_next().await? {
+        match format {
+            ExportFormat::Jsonl => {
+                serde_json::to_writer(&mut *out, &row)?;
+                writeln!(out)?;
+            }
+            ExportFormat::Csv => {
+                let mut fields = match serde_json::to_value(&row)? {
+                    serde_json::Value::Object(o) => o,
+                    _ => unreachable!("rows are structs"),
+                };
+                write_csv_line(
+                    out,
+                    R::COLUMNS.iter().map(|c| match fields.remove(*c) {
+                        Some(serde_json::Value::String(s)) => s,
+                        Some(serde_json::Value::Null) | None => String::new(),
+                        Some(v) => v.to_string(),
+                    }),
+                )?;
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn write_csv_line(
+    out: &mut impl Write,
+    fields: impl Iterator<Item = String>,
+) -> Result<(), std::io::Error> {
+    let line = fields.map(csv_field).collect::<Vec<_>>().join(",");
+    write!(out, "{}\r\n", line)
+}
+
+/// quote it if it needs quoting. anything a spreadsheet would run as a formula gets a `'`
+/// in front, since names and reasons come from players
+fn csv_field(mut s: String) -> String {
+    if s.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        s.insert(0, '\'');
+    }
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_field;
+
+    #[test]
+    fn csv_fields_are_escaped() {
+        assert_eq!(csv_field("plain".to_string()), "plain");
+        assert_eq!(csv_field("a, b".to_string()), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\"".to_string()), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines".to_string()), "\"two\nlines\"");
+        assert_eq!(csv_field("=1+1".to_string()), "'=1+1");
+    }
+}
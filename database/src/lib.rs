@@ -1,12 +1,19 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use common::{Action, Location, Report, ReportStatus};
+use common::{Action, Location, Punishment, Report, ReportStatus};
 use sqlx::{
     migrate,
     sqlite::{SqliteConnectOptions, SqlitePoolOptions},
-    SqlitePool,
+    SqliteConnection, SqlitePool,
 };
 
+mod export;
+pub use export::{ExportFilter, ExportFormat, ExportTable};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Database error: {0}")]
@@ -23,23 +30,164 @@ pub enum Error {
     ActionNotFound(u32),
     #[error("Foreign Key Error: {0}")]
     ForeignKeyError(String),
+    #[error("Invalid outbox item: {0}")]
+    InvalidOutboxItem(i64),
+    #[error("Failed to back up before migrating, not touching the database: {0}")]
+    PreMigrationBackup(Box<Error>),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to write JSON: {0}")]
+    Json(#[from] serde_json::Error),
 }
 
+/// where the database lives, relative to the working directory
+pub const DB_FILE: &str = "lurk_chan.db";
+/// where backups go, relative to the working directory
+pub const BACKUP_DIR: &str = "backups";
+/// who made an imported audit when nobody on discord did, like bans from SL's ban files.
+/// they're left off leaderboards
+pub const UNKNOWN_CLAIMANT: u64 = 0;
+
 pub struct Database {
     pool: SqlitePool,
 }
 
 impl Database {
     pub async fn new() -> Result<Self, Error> {
-        let options = SqliteConnectOptions::new()
-            .create_if_missing(true)
-            .filename("lurk_chan.db");
-        let pool = SqlitePoolOptions::new().connect_with(options).await?;
-        migrate!().run(&pool).await?;
-        let db = Self { pool };
+        let db = Self::connect(DB_FILE, true).await?;
+        db.pre_migration_backup().await?;
+        db.migrate().await?;
         db.vacuum().await?;
         Ok(db)
     }
+    /// open a database that already exists, without migrating it
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::connect(path, false).await
+    }
+    async fn connect(path: impl AsRef<Path>, create: bool) -> Result<Self, Error> {
+        let options = SqliteConnectOptions::new()
+            .create_if_missing(create)
+            .filename(path);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        Ok(Self { pool })
+    }
+    /// close every connection, flushing the WAL into the main file
+    pub async fn close(self) -> Result<(), Error> {
+        sqlx::query("pragma wal_checkpoint(truncate);")
+            .execute(&self.pool)
+            .await?;
+        self.pool.close().await;
+        Ok(())
+    }
+    pub async fn migrate(&self) -> Result<(), Error> {
+        migrate!().run(&self.pool).await?;
+        self.classify_punishments().await?;
+        Ok(())
+    }
+    /// guess the punishment of audits that don't have one yet from their action text.
+    /// ones it can't tell are marked `other`, so they're only looked at once
+    async fn classify_punishments(&self) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        let todo = sqlx::query!(r#"select id as "id!", action from Actions where punishment is null"#)
+            .fetch_all(&mut *tx)
+            .await?;
+        if todo.is_empty() {
+            return Ok(());
+        }
+        let mut known = 0;
+        for a in &todo {
+            let p = Punishment::parse(&a.action);
+            known += p.is_some() as usize;
+            let (kind, secs) = punishment_to_db(p);
+            sqlx::query!(
+                "update Actions set punishment = ?, punishment_secs = ? where id = ?",
+                kind,
+                secs,
+                a.id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        tracing::info!(
+            "Worked out the punishment of {} of {} old audit(s)",
+            known,
+            todo.len()
+        );
+        Ok(())
+    }
+    /// the migrations [`Database::migrate`] would run, oldest first
+    pub async fn pending_migrations(&self) -> Result<Vec<String>, Error> {
+        let applied: HashSet<i64> = self
+            .applied_migrations()
+            .await?
+            .into_iter()
+            .map(|(v, _)| v)
+            .collect();
+        Ok(migrate!()
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+            .map(|m| format!("{} {}", m.version, m.description))
+            .collect())
+    }
+    /// migrations that have been run, as (version, description), oldest first
+    pub async fn applied_migrations(&self) -> Result<Vec<(i64, String)>, Error> {
+        // a brand new database doesn't have the table yet. anything else going wrong isn't a
+        // fresh database, and shouldn't be migrated without a backup
+        let exists: Option<(i64,)> = sqlx::query_as(
+            "select 1 from sqlite_master where type = 'table' and name = '_sqlx_migrations'",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if exists.is_none() {
+            return Ok(vec![]);
+        }
+        Ok(sqlx::query_as(
+            "select version, description from _sqlx_migrations where success order by version",
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+    /// back up a database that's about to be migrated, so a bad migration can be undone.
+    /// None if there's nothing to migrate, or nothing worth keeping
+    pub async fn pre_migration_backup(&self) -> Result<Option<PathBuf>, Error> {
+        if self.pending_migrations().await?.is_empty()
+            || self.applied_migrations().await?.is_empty()
+        {
+            return Ok(None);
+        }
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = PathBuf::from(BACKUP_DIR).join(format!("pre-migration_{}.db", secs));
+        let backup = async {
+            std::fs::create_dir_all(BACKUP_DIR)?;
+            self.backup_to(path.clone()).await
+        };
+        backup
+            .await
+            .map_err(|e| Error::PreMigrationBackup(Box::new(e)))?;
+        tracing::info!(
+            "Backed the database up to {} before migrating",
+            path.display()
+        );
+        Ok(Some(path))
+    }
+    /// undo every migration newer than `version` with its `.down.sql`.
+    /// returns what was undone, newest first
+    pub async fn rollback(&self, version: i64) -> Result<Vec<String>, Error> {
+        let undone = self
+            .applied_migrations()
+            .await?
+            .into_iter()
+            .rev()
+            .filter(|(v, _)| *v > version)
+            .map(|(v, d)| format!("{} {}", v, d))
+            .collect();
+        migrate!().undo(&self.pool, version).await?;
+        Ok(undone)
+    }
     pub async fn vacuum(&self) -> Result<(), Error> {
         sqlx::query("vacuum;").execute(&self.pool).await?;
         Ok(())
@@ -48,12 +196,129 @@ impl Database {
         sqlx::query("PRAGMA optimize;").execute(&self.pool).await?;
         Ok(())
     }
-    pub async fn backup_to(&self, huh: PathBuf) -> Result<(), Error> {
-        sqlx::query(&format!("vacuum into '{}';", huh.display()))
+    /// copy the whole database into a new file. fails if the file is already there
+    pub async fn backup_to(&self, path: PathBuf) -> Result<(), Error> {
+        sqlx::query("vacuum into ?;")
+            .bind(path.to_string_lossy())
             .execute(&self.pool)
             .await?;
         Ok(())
     }
+    /// where a guild wants things posted. None if it was never set up
+    pub async fn guild_settings(&self, guild: u64) -> Result<Option<GuildSettings>, Error> {
+        let g = guild.to_string();
+        let res = sqlx::query!(
+            "select sl_audit, discord_audit, discord_reports, stats from GuildSettings where guild_id = ?",
+            g
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(r) = res else {
+            return Ok(None);
+        };
+        Ok(Some(GuildSettings {
+            guild_id: guild,
+            sl_audit: parse_opt(r.sl_audit)?,
+            discord_audit: parse_opt(r.discord_audit)?,
+            discord_reports: parse_opt(r.discord_reports)?,
+            stats: parse_opt(r.stats)?,
+        }))
+    }
+    /// every guild that was set up
+    pub async fn all_guild_settings(&self) -> Result<Vec<GuildSettings>, Error> {
+        let res = sqlx::query!(
+            "select guild_id, sl_audit, discord_audit, discord_reports, stats from GuildSettings"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        res.into_iter()
+            .map(|r| {
+                Ok(GuildSettings {
+                    guild_id: r.guild_id.parse()?,
+                    sl_audit: parse_opt(r.sl_audit)?,
+                    discord_audit: parse_opt(r.discord_audit)?,
+                    discord_reports: parse_opt(r.discord_reports)?,
+                    stats: parse_opt(r.stats)?,
+                })
+            })
+            .collect()
+    }
+    pub async fn set_guild_settings(&self, settings: &GuildSettings) -> Result<(), Error> {
+        let s = |i: Option<u64>| i.map(|i| i.to_string());
+        let (g, sl_audit, discord_audit, discord_reports, stats) = (
+            settings.guild_id.to_string(),
+            s(settings.sl_audit),
+            s(settings.discord_audit),
+            s(settings.discord_reports),
+            s(settings.stats),
+        );
+        sqlx::query!(
+            "insert or replace into GuildSettings(guild_id, sl_audit, discord_audit, discord_reports, stats) values (?,?,?,?,?)",
+            g,
+            sl_audit,
+            discord_audit,
+            discord_reports,
+            stats
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    /// every `/config` value a guild changed, as (key, value)
+    pub async fn settings(&self, guild: u64) -> Result<Vec<(String, String)>, Error> {
+        let g = guild.to_string();
+        let res = sqlx::query!("select key, value from Settings where guild_id = ?", g)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(res.into_iter().map(|r| (r.key, r.value)).collect())
+    }
+    pub async fn set_setting(
+        &self,
+        guild: u64,
+        key: &str,
+        value: &str,
+        who: u64,
+    ) -> Result<(), Error> {
+        let (g, who) = (guild.to_string(), who.to_string());
+        sqlx::query!(
+            "insert or replace into Settings(guild_id, key, value, updated_by, updated_at) values (?,?,?,?,strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))",
+            g,
+            key,
+            value,
+            who
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    /// back to the default. false if it wasn't set
+    pub async fn reset_setting(&self, guild: u64, key: &str) -> Result<bool, Error> {
+        let g = guild.to_string();
+        let res = sqlx::query!(
+            "delete from Settings where guild_id = ? and key = ?",
+            g,
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+    /// hand every report and audit from before guilds were tracked to `guild`.
+    /// returns how many reports and audits that was
+    pub async fn adopt_guildless(&self, guild: u64) -> Result<(u64, u64), Error> {
+        let g = guild.to_string();
+        let mut tx = self.pool.begin().await?;
+        let reports = sqlx::query!("update Reports set guild_id = ? where guild_id is null", g)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let actions = sqlx::query!("update Actions set guild_id = ? where guild_id is null", g)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok((reports, actions))
+    }
     pub async fn get_report_from_id(&self, report_id: u32) -> Result<Option<Report>, Error> {
         let report_id = report_id as i64;
         let res: Option<DBReport> =
@@ -66,10 +331,31 @@ impl Database {
         }
     }
     pub async fn add_report(&self, report: Report) -> Result<u32, Error> {
-        let r = DBReport::from_report(report);
+        self.insert_report(DBReport::from_report(report), false).await
+    }
+    /// add a report that came from a webhook message.
+    /// None if that message was already turned into a report
+    pub async fn add_report_from_message(
+        &self,
+        report: Report,
+        channel_id: u64,
+        message_id: u64,
+    ) -> Result<Option<u32>, Error> {
+        let mut r = DBReport::from_report(report);
+        r.source_channel = Some(channel_id.to_string());
+        r.source_message = Some(message_id.to_string());
+        match self.insert_report(r, true).await {
+            Ok(id) => Ok(Some(id)),
+            Err(Error::Database(sqlx::Error::Database(e))) if e.is_unique_violation() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    /// `posting` if whoever's adding it is about to post its message, see [`Database::claim_report_post`]
+    async fn insert_report(&self, r: DBReport, posting: bool) -> Result<u32, Error> {
+        let mut tx = self.pool.begin().await?;
         let res =
             sqlx::query!(
-                "insert into Reports(reporter_id, reporter_name, reported_id, reported_name, report_reason, report_status, server, time, claimant, location) values (?,?,?,?,?,?,?,?,?,?)", 
+                "insert into Reports(reporter_id, reporter_name, reported_id, reported_name, report_reason, report_status, server, time, claimant, location, source_channel, source_message, guild_id) values (?,?,?,?,?,?,?,?,?,?,?,?,?)", 
                 r.reporter_id,
                 r.reporter_name,
                 r.reported_id,
@@ -79,9 +365,184 @@ impl Database {
                 r.server,
                 r.time,
                 r.claimant,
-                r.location
-            ).execute(&self.pool).await?;
-        Ok(res.last_insert_rowid() as u32)
+                r.location,
+                r.source_channel,
+                r.source_message,
+                r.guild_id
+            ).execute(&mut *tx).await?;
+        let id = res.last_insert_rowid();
+        record_event(&mut tx, id, None, None, &r.report_status, None).await?;
+        if posting {
+            sqlx::query!("insert into ReportPosts(report_id) values (?)", id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(id as u32)
+    }
+    /// take over posting report `id`'s message, if nobody is or they've had it for long enough
+    /// that they must have died. false if someone else is on it
+    pub async fn claim_report_post(&self, id: u32) -> Result<bool, Error> {
+        let id = id as i64;
+        let res = sqlx::query!(
+            "insert into ReportPosts(report_id) values (?1)
+            on conflict(report_id) do update set claimed_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now')
+            where julianday(claimed_at) < julianday('now', '-5 minutes')",
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+    /// add a report from an import, claim and close times included, and remember where it came from.
+    /// None if something with `key` was imported before
+    pub async fn import_report(
+        &self,
+        report: Report,
+        source: &str,
+        key: &str,
+    ) -> Result<Option<u32>, Error> {
+        let r = DBReport::from_report(report);
+        let mut tx = self.pool.begin().await?;
+        if was_imported(&mut tx, key).await? {
+            return Ok(None);
+        }
+        let id = sqlx::query!(
+            "insert into Reports(reporter_id, reporter_name, reported_id, reported_name, report_reason, report_status, server, time, claimant, location, claimed_at, closed_at, guild_id) values (?,?,?,?,?,?,?,?,?,?,?,?,?)",
+            r.reporter_id,
+            r.reporter_name,
+            r.reported_id,
+            r.reported_name,
+            r.report_reason,
+            r.report_status,
+            r.server,
+            r.time,
+            r.claimant,
+            r.location,
+            r.claimed_at,
+            r.closed_at,
+            r.guild_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        record_event(&mut tx, id, None, None, &r.report_status, Some("Imported")).await?;
+        record_import(&mut tx, key, "report", id, source).await?;
+        tx.commit().await?;
+        Ok(Some(id as u32))
+    }
+    /// add an audit from an import, and remember where it came from. unlike [`Database::add_action`]
+    /// it isn't posted anywhere. None if something with `key` was imported before
+    pub async fn import_action(
+        &self,
+        action: Action,
+        source: &str,
+        key: &str,
+    ) -> Result<Option<u32>, Error> {
+        let a = DBAction::from(action);
+        let mut tx = self.pool.begin().await?;
+        if was_imported(&mut tx, key).await? {
+            return Ok(None);
+        }
+        let id = sqlx::query!(
+            "insert into Actions(target_id, target_username, offense, action, server, claimant, report, created_at, guild_id, punishment, punishment_secs) values (?,?,?,?,?,?,?,?,?,?,?)",
+            a.target_id,
+            a.target_username,
+            a.offense,
+            a.action,
+            a.server,
+            a.claimant,
+            a.report,
+            a.created_at,
+            a.guild_id,
+            a.punishment,
+            a.punishment_secs
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        record_import(&mut tx, key, "audit", id, source).await?;
+        tx.commit().await?;
+        Ok(Some(id as u32))
+    }
+    /// whether something with `key` was imported before
+    pub async fn is_imported(&self, key: &str) -> Result<bool, Error> {
+        let mut conn = self.pool.acquire().await?;
+        was_imported(&mut conn, key).await
+    }
+    /// where a report was imported from. None if it wasn't
+    pub async fn report_import_source(&self, id: u32) -> Result<Option<String>, Error> {
+        let id = id as i64;
+        Ok(sqlx::query_scalar!(
+            "select source from Imports where kind = 'report' and row_id = ?",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+    /// where an audit was imported from. None if it wasn't
+    pub async fn action_import_source(&self, id: u32) -> Result<Option<String>, Error> {
+        let id = id as i64;
+        Ok(sqlx::query_scalar!(
+            "select source from Imports where kind = 'audit' and row_id = ?",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+    /// every report message we know about, as (report id, channel, message)
+    pub async fn all_report_messages(&self) -> Result<Vec<(u32, u64, u64)>, Error> {
+        let res = sqlx::query!("select report_id, channel, message from ReportMessages")
+            .fetch_all(&self.pool)
+            .await?;
+        res.into_iter()
+            .map(|i| Ok((i.report_id as u32, i.channel.parse()?, i.message.parse()?)))
+            .collect()
+    }
+    /// every audit message we know about, as (audit id, channel, message)
+    pub async fn all_action_messages(&self) -> Result<Vec<(u32, u64, u64)>, Error> {
+        let res = sqlx::query!("select action_id, channel, message from ActionMessages")
+            .fetch_all(&self.pool)
+            .await?;
+        res.into_iter()
+            .map(|i| Ok((i.action_id as u32, i.channel.parse()?, i.message.parse()?)))
+            .collect()
+    }
+    /// the newest webhook message in `channel_id` that was turned into a report
+    pub async fn last_source_message(&self, channel_id: u64) -> Result<Option<u64>, Error> {
+        let c = channel_id.to_string();
+        let res = sqlx::query_scalar!(
+            r#"select max(cast(source_message as integer)) as "last: i64" from Reports where source_channel = ?"#,
+            c
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(res.map(|i| i as u64))
+    }
+    /// the report that came in as `message` in `channel`, if it was ingested
+    pub async fn report_from_source(
+        &self,
+        channel_id: u64,
+        message_id: u64,
+    ) -> Result<Option<u32>, Error> {
+        let (c, m) = (channel_id.to_string(), message_id.to_string());
+        let res = sqlx::query_scalar!(
+            r#"select id as "id!" from Reports where source_channel = ? and source_message = ?"#,
+            c,
+            m
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(res.map(|i| i as u32))
+    }
+    /// every channel reports have come from or been posted in
+    pub async fn report_channels(&self) -> Result<Vec<u64>, Error> {
+        let res = sqlx::query_scalar!(
+            r#"select channel as "channel!" from ReportMessages union select source_channel from Reports where source_channel is not null"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        res.into_iter().map(|i| Ok(i.parse()?)).collect()
     }
     pub async fn get_action_from_id(&self, id: u32) -> Result<Option<Action>, Error> {
         let action_id = id as i64;
@@ -94,8 +555,9 @@ impl Database {
             None => Ok(None),
         }
     }
-    pub async fn report_count_no_audit(&self) -> Result<u32, Error> {
-        let res: i64 = sqlx::query_scalar!("select count(*) from Reports R left join Actions A on A.report == R.id where A.report is null;").fetch_one(&self.pool).await?;
+    pub async fn report_count_no_audit(&self, guild: u64) -> Result<u32, Error> {
+        let g = guild.to_string();
+        let res: i64 = sqlx::query_scalar!("select count(*) from Reports R left join Actions A on A.report == R.id where A.report is null and R.guild_id = ?;", g).fetch_one(&self.pool).await?;
         Ok(res as u32)
     }
     pub async fn add_report_message(
@@ -110,7 +572,7 @@ impl Database {
             message_id.to_string(),
         );
         sqlx::query!(
-            "insert into ReportMessages(report_id, channel, message) values (?,?,?)",
+            "insert or replace into ReportMessages(report_id, channel, message) values (?,?,?)",
             a,
             b,
             c
@@ -119,11 +581,20 @@ impl Database {
         .await?;
         Ok(())
     }
-    pub async fn report_count_from_server(&self, server: Location) -> Result<u32, Error> {
+    pub async fn report_count_from_server(
+        &self,
+        server: Location,
+        guild: u64,
+    ) -> Result<u32, Error> {
         let s = server.to_string();
-        let res: i64 = sqlx::query_scalar!("select count(*) from Reports where location = ?", s)
-            .fetch_one(&self.pool)
-            .await?;
+        let g = guild.to_string();
+        let res: i64 = sqlx::query_scalar!(
+            "select count(*) from Reports where location = ? and guild_id = ?",
+            s,
+            g
+        )
+        .fetch_one(&self.pool)
+        .await?;
         Ok(res as u32)
     }
     pub async fn add_action_message(
@@ -175,38 +646,136 @@ impl Database {
             None => Ok(None),
         }
     }
-    pub async fn audit_count_from_server(&self, server: Location) -> Result<u32, Error> {
+    pub async fn audit_count_from_server(
+        &self,
+        server: Location,
+        guild: u64,
+    ) -> Result<u32, Error> {
         let s = server.to_string();
-        let res: i64 = sqlx::query_scalar!("select count(*) from Actions where server = ?", s)
-            .fetch_one(&self.pool)
-            .await?;
+        let g = guild.to_string();
+        let res: i64 = sqlx::query_scalar!(
+            "select count(*) from Actions where server = ? and guild_id = ?",
+            s,
+            g
+        )
+        .fetch_one(&self.pool)
+        .await?;
         Ok(res as u32)
     }
-    pub async fn audit_count_without_report(&self) -> Result<u32, Error> {
-        let res: i64 = sqlx::query_scalar!("select count(*) from Actions where report is null")
-            .fetch_one(&self.pool)
-            .await?;
+    pub async fn audit_count_without_report(&self, guild: u64) -> Result<u32, Error> {
+        let g = guild.to_string();
+        let res: i64 = sqlx::query_scalar!(
+            "select count(*) from Actions where report is null and guild_id = ?",
+            g
+        )
+        .fetch_one(&self.pool)
+        .await?;
         Ok(res as u32)
     }
+    /// the message of the newest audit for a report. a report that was reopened and closed again
+    /// has one audit per close, and the last one is the one that counts
     pub async fn get_action_message_from_report_id(
         &self,
         id: u32,
     ) -> Result<Option<(u64, u64)>, Error> {
         let id = id as i64;
-        let res: Option<(String, String)> = sqlx::query!("select channel, message from ActionMessages where action_id = (select id from Actions where report = ?)", id)
+        let res: Option<(String, String)> = sqlx::query!("select channel, message from ActionMessages where action_id = (select id from Actions where report = ? order by id desc limit 1)", id)
             .fetch_optional(&self.pool).await.map(|i| i.map(|i| (i.channel, i.message)))?;
         match res {
             Some((a, b)) => Ok(Some((a.parse()?, b.parse()?))),
             None => Ok(None),
         }
     }
-    pub async fn get_report_count(&self, id: &str) -> Result<u32, Error> {
-        let res: i64 =
-            sqlx::query_scalar!("select count(*) from Reports where reported_id = ?", id)
-                .fetch_one(&self.pool)
-                .await?;
+    /// how many times someone was reported. in `guild`, or anywhere if None
+    pub async fn get_report_count(&self, id: &str, guild: Option<u64>) -> Result<u32, Error> {
+        let g = guild.map(|g| g.to_string());
+        let res: i64 = sqlx::query_scalar!(
+            "select count(*) from Reports where reported_id = ?1 and (?2 is null or guild_id = ?2)",
+            id,
+            g
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(res as u32)
+    }
+    /// how many times someone was audited. in `guild`, or anywhere if None
+    pub async fn get_action_count(&self, id: &str, guild: Option<u64>) -> Result<u32, Error> {
+        let g = guild.map(|g| g.to_string());
+        let res: i64 = sqlx::query_scalar!(
+            "select count(*) from Actions where target_id = ?1 and (?2 is null or guild_id = ?2)",
+            id,
+            g
+        )
+        .fetch_one(&self.pool)
+        .await?;
         Ok(res as u32)
     }
+    /// punishments in `guild` that haven't run out, including permanent bans.
+    /// the ones running out soonest come first, then permanent bans, newest first
+    pub async fn active_punishments(&self, guild: u64) -> Result<Vec<ActivePunishment>, Error> {
+        let g = guild.to_string();
+        let res = sqlx::query_as!(
+            DBAction,
+            "select * from Actions
+            where guild_id = ? and (punishment = 'permanent_ban' or julianday(expires_at) > julianday('now'))
+            order by expires_at is null, julianday(expires_at), id desc",
+            g
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        res.into_iter().map(TryInto::try_into).collect()
+    }
+    /// punishments that ran out after `since` (RFC 3339) that nobody was told about yet, oldest first
+    pub async fn ended_punishments(&self, since: &str) -> Result<Vec<ActivePunishment>, Error> {
+        let res = sqlx::query_as!(
+            DBAction,
+            "select * from Actions A
+            where julianday(A.expires_at) <= julianday('now') and julianday(A.expires_at) > julianday(?)
+            and not exists (select 1 from PunishmentNotices N where N.action_id = A.id)
+            order by julianday(A.expires_at)",
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        res.into_iter().map(TryInto::try_into).collect()
+    }
+    /// remember that audit `id` was announced as over, and the `(channel, message)` saying so
+    pub async fn set_punishment_noticed(
+        &self,
+        id: u32,
+        message: Option<(u64, u64)>,
+    ) -> Result<(), Error> {
+        let id = id as i64;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "insert or ignore into PunishmentNotices(action_id) values (?)",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if let Some((channel, message)) = message {
+            let (c, m) = (channel.to_string(), message.to_string());
+            sqlx::query!(
+                "insert or ignore into NoticeMessages(action_id, channel, message) values (?,?,?)",
+                id,
+                c,
+                m
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+    /// every message announcing a punishment ran out, as (channel, message)
+    pub async fn all_notice_messages(&self) -> Result<Vec<(u64, u64)>, Error> {
+        let res = sqlx::query!("select channel, message from NoticeMessages")
+            .fetch_all(&self.pool)
+            .await?;
+        res.into_iter()
+            .map(|i| Ok((i.channel.parse()?, i.message.parse()?)))
+            .collect()
+    }
     pub async fn leaderboard_reports(&self, limit: u32) -> Result<Vec<(u64, u32)>, Error> {
         let res = sqlx::query!(
             "select claimant, count(*) as count from Reports where claimant is not null group by claimant order by count desc limit ?",
@@ -220,32 +789,130 @@ impl Database {
             .map(|(a, b)| (a.expect("claimant is not null").parse().unwrap(), b as u32))
             .collect())
     }
-    pub async fn all_reports_with_status(
+    /// every report, oldest first
+    pub async fn all_reports(&self) -> Result<Vec<(u32, Report)>, Error> {
+        let res = sqlx::query_as!(DBReport, "select * from Reports order by id")
+            .fetch_all(&self.pool)
+            .await?;
+        res.into_iter()
+            .map(|i| Ok((i.id.unwrap() as u32, i.into_report()?)))
+            .collect()
+    }
+    /// every audit, oldest first
+    pub async fn all_actions(&self) -> Result<Vec<(u32, Action)>, Error> {
+        let res = sqlx::query_as!(DBAction, "select * from Actions order by id")
+            .fetch_all(&self.pool)
+            .await?;
+        res.into_iter()
+            .map(|i| Ok((i.id.unwrap() as u32, i.try_into()?)))
+            .collect()
+    }
+    /// every report with `status` that isn't quarantined. a row that can't be read comes back
+    /// as an error, so one bad report doesn't hide the rest
+    pub async fn reports_to_expire(
         &self,
         status: ReportStatus,
-    ) -> Result<Vec<(u32, Report)>, Error> {
+    ) -> Result<Vec<(u32, Result<Report, Error>)>, Error> {
         let s = status.to_db();
-        let res = sqlx::query_as!(DBReport, "select * from Reports where report_status = ?", s)
+        let res = sqlx::query_as!(
+            DBReport,
+            "select * from Reports where report_status = ? and id not in (select report_id from Quarantine)",
+            s
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res
+            .into_iter()
+            .map(|i| (i.id.unwrap() as u32, i.into_report()))
+            .collect())
+    }
+    /// keep the expiry task away from a report it can't deal with
+    pub async fn quarantine_report(&self, rid: u32, reason: &str) -> Result<(), Error> {
+        let rid = rid as i64;
+        sqlx::query!(
+            "insert or replace into Quarantine(report_id, reason) values (?, ?)",
+            rid,
+            reason
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    /// let the expiry task look at a report again. false if it wasn't quarantined
+    pub async fn release_quarantine(&self, rid: u32) -> Result<bool, Error> {
+        let rid = rid as i64;
+        let res = sqlx::query!("delete from Quarantine where report_id = ?", rid)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+    /// quarantined reports, as (report id, reason, when)
+    pub async fn quarantined_reports(&self) -> Result<Vec<(u32, String, String)>, Error> {
+        let res = sqlx::query!(
+            r#"select report_id as "report_id!", reason, quarantined_at from Quarantine order by report_id"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res
+            .into_iter()
+            .map(|i| (i.report_id as u32, i.reason, i.quarantined_at))
+            .collect())
+    }
+    /// when each report was last warned about, as the `since` passed to [`Database::set_expiry_warning`]
+    pub async fn expiry_warnings(&self) -> Result<HashMap<u32, String>, Error> {
+        let res = sqlx::query!(r#"select report_id as "report_id!", since from ExpiryWarnings"#)
             .fetch_all(&self.pool)
             .await?;
         Ok(res
             .into_iter()
-            .map(|i| (i.id.unwrap() as u32, i.into_report().unwrap()))
+            .map(|i| (i.report_id as u32, i.since))
             .collect())
     }
-    pub async fn expire_report(&self, rid: u32) -> Result<(), Error> {
+    pub async fn set_expiry_warning(&self, rid: u32, since: &str) -> Result<(), Error> {
         let rid = rid as i64;
         sqlx::query!(
-            "update Reports set report_status = 'expired' where id = ?",
-            rid
+            "insert or replace into ExpiryWarnings(report_id, since) values (?, ?)",
+            rid,
+            since
         )
         .execute(&self.pool)
         .await?;
         Ok(())
     }
+    pub async fn expire_report(&self, rid: u32) -> Result<Outcome, Error> {
+        self.expire_report_from(rid, "open", None).await
+    }
+    /// expire a claimed report nobody has done anything with for too long
+    pub async fn expire_stale_claim(&self, rid: u32) -> Result<Outcome, Error> {
+        self.expire_report_from(rid, "claimed", Some("Claimed, but left alone for too long"))
+            .await
+    }
+    async fn expire_report_from(
+        &self,
+        rid: u32,
+        from: &str,
+        note: Option<&str>,
+    ) -> Result<Outcome, Error> {
+        let rid = rid as i64;
+        let mut tx = self.pool.begin().await?;
+        // someone might have done something with it since we looked
+        let res = sqlx::query!(
+            "update Reports set report_status = 'expired' where id = ? and report_status = ?",
+            rid,
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return outcome_of(&mut tx, rid).await;
+        }
+        record_event(&mut tx, rid, None, Some(from), "expired", note).await?;
+        tx.commit().await?;
+        Ok(Outcome::Done)
+    }
     pub async fn leaderboard_audit(&self, limit: u32) -> Result<Vec<(u64, u32)>, Error> {
         let res = sqlx::query!(
-            "select claimant, count(*) as count from Actions where claimant is not null group by claimant order by count desc limit ?",
+            "select claimant, count(*) as count from Actions where claimant is not null and claimant != '0' group by claimant order by count desc limit ?",
             limit
         )
         .fetch_all(&self.pool)
@@ -254,17 +921,127 @@ impl Database {
         .map(|i| (i.claimant, i.count));
         Ok(res.map(|(a, b)| (a.parse().unwrap(), b as u32)).collect())
     }
-    pub async fn claim_report(&self, id: u32, claimant: u64) -> Result<(), Error> {
+    /// like [`Database::leaderboard_reports`], but only counting reports claimed between `since` and `until`
+    /// (RFC 3339 timestamps, None for no limit).
+    /// reports from before claim times were tracked go by when they came in instead.
+    /// returns how many staff members are on the board along with the requested slice of it
+    pub async fn leaderboard_reports_between(
+        &self,
+        guild: u64,
+        since: Option<&str>,
+        until: Option<&str>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(u32, Vec<(u64, u32)>), Error> {
+        let g = guild.to_string();
+        let (total, res) = tokio::try_join!(
+            sqlx::query_scalar!(
+                "select count(distinct claimant) from Reports where claimant is not null and guild_id = ?3
+                and (?1 is null or julianday(coalesce(claimed_at, time)) >= julianday(?1))
+                and (?2 is null or julianday(coalesce(claimed_at, time)) < julianday(?2))",
+                since,
+                until,
+                g
+            )
+            .fetch_one(&self.pool),
+            sqlx::query!(
+                r#"select claimant as "claimant!", count(*) as "count!: i64" from Reports where claimant is not null and guild_id = ?5
+                and (?1 is null or julianday(coalesce(claimed_at, time)) >= julianday(?1))
+                and (?2 is null or julianday(coalesce(claimed_at, time)) < julianday(?2))
+                group by claimant order by count(*) desc, claimant limit ?3 offset ?4"#,
+                since,
+                until,
+                limit,
+                offset,
+                g
+            )
+            .fetch_all(&self.pool)
+        )?;
+        let res = res
+            .into_iter()
+            .map(|i| Ok((i.claimant.parse()?, i.count as u32)))
+            .collect::<Result<_, Error>>()?;
+        Ok((total as u32, res))
+    }
+    /// like [`Database::leaderboard_audit`], but only counting audits made between `since` and `until`
+    /// (RFC 3339 timestamps, None for no limit).
+    /// audits from before creation times were tracked go by their report, if they have one.
+    /// returns how many staff members are on the board along with the requested slice of it
+    pub async fn leaderboard_audit_between(
+        &self,
+        guild: u64,
+        since: Option<&str>,
+        until: Option<&str>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(u32, Vec<(u64, u32)>), Error> {
+        let g = guild.to_string();
+        let (total, res) = tokio::try_join!(
+            sqlx::query_scalar!(
+                "select count(distinct claimant) from Actions A
+                where A.guild_id = ?3 and A.claimant != '0'
+                and (?1 is null or julianday(coalesce(A.created_at, (select time from Reports R where R.id = A.report))) >= julianday(?1))
+                and (?2 is null or julianday(coalesce(A.created_at, (select time from Reports R where R.id = A.report))) < julianday(?2))",
+                since,
+                until,
+                g
+            )
+            .fetch_one(&self.pool),
+            sqlx::query!(
+                r#"select claimant, count(*) as "count!: i64" from Actions A
+                where A.guild_id = ?5 and A.claimant != '0'
+                and (?1 is null or julianday(coalesce(A.created_at, (select time from Reports R where R.id = A.report))) >= julianday(?1))
+                and (?2 is null or julianday(coalesce(A.created_at, (select time from Reports R where R.id = A.report))) < julianday(?2))
+                group by claimant order by count(*) desc, claimant limit ?3 offset ?4"#,
+                since,
+                until,
+                limit,
+                offset,
+                g
+            )
+            .fetch_all(&self.pool)
+        )?;
+        let res = res
+            .into_iter()
+            .map(|i| Ok((i.claimant.parse()?, i.count as u32)))
+            .collect::<Result<_, Error>>()?;
+        Ok((total as u32, res))
+    }
+    /// the status of a report if it's one of `allowed`, otherwise why it isn't
+    async fn current_status(
+        &self,
+        id: i64,
+        allowed: &[&str],
+    ) -> Result<Result<String, Outcome>, Error> {
+        let mut conn = self.pool.acquire().await?;
+        match status_of(&mut conn, id).await? {
+            Some(s) if allowed.contains(&s.as_str()) => Ok(Ok(s)),
+            _ => Ok(Err(outcome_of(&mut conn, id).await?)),
+        }
+    }
+    /// claim an open (or expired) report. only one person can win if two people click at once
+    pub async fn claim_report(&self, id: u32, claimant: u64) -> Result<Outcome, Error> {
         let id = id as i64;
+        let old = match self.current_status(id, &["open", "expired"]).await? {
+            Ok(s) => s,
+            Err(o) => return Ok(o),
+        };
         let c = claimant.to_string();
-        sqlx::query!(
-            "update Reports set report_status = 'claimed', claimant = ? where id = ?",
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            "update Reports set report_status = 'claimed', claimant = ?1, claimed_at = coalesce(claimed_at, strftime('%Y-%m-%dT%H:%M:%SZ', 'now')) where id = ?2 and report_status = ?3",
             c,
-            id
+            id,
+            old
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
-        Ok(())
+        if res.rows_affected() == 0 {
+            return outcome_of(&mut tx, id).await;
+        }
+        record_event(&mut tx, id, Some(claimant), Some(&old), "claimed", None).await?;
+        tx.commit().await?;
+        Ok(Outcome::Done)
     }
     pub async fn who_claimed_report(&self, id: u32) -> Result<Option<u64>, Error> {
         let id = id as i64;
@@ -278,74 +1055,381 @@ impl Database {
             None => Ok(None),
         }
     }
+    /// add an audit. its message gets posted through the outbox
     pub async fn add_action(&self, action: Action) -> Result<u32, Error> {
-        let a = DBAction::from(action);
-        let res =
-            sqlx::query!(
-                "insert into Actions(target_id, target_username, offense, action, server, claimant, report) values (?,?,?,?,?,?,?)", 
-                a.target_id,
-                a.target_username,
-                a.offense,
-                a.action,
-                a.server,
-                a.claimant,
-                a.report
-            ).execute(&self.pool).await?;
-        Ok(res.last_insert_rowid() as u32)
-    }
-    pub async fn close_report(&self, id: u32) -> Result<(), Error> {
-        let id = id as i64;
+        let mut tx = self.pool.begin().await?;
+        let id = insert_action(&mut tx, action).await?;
+        enqueue(&mut tx, &SideEffect::PostAudit(id)).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+    /// change what an audit's punishment was, and nothing else about it. its message gets
+    /// updated through the outbox
+    pub async fn set_punishment(&self, id: u32, punishment: Option<Punishment>) -> Result<(), Error> {
+        let (kind, secs) = punishment_to_db(punishment);
+        let aid = id as i64;
+        let mut tx = self.pool.begin().await?;
         sqlx::query!(
-            "update Reports set report_status = 'closed' where id = ?",
-            id
+            "update Actions set punishment = ?, punishment_secs = ? where id = ?",
+            kind,
+            secs,
+            aid
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        enqueue(&mut tx, &SideEffect::UpdateAudit(id)).await?;
+        tx.commit().await?;
         Ok(())
     }
+    /// close a claimed report. if `claimant` is set, only if they are the one who claimed it
+    pub async fn close_report(
+        &self,
+        id: u32,
+        who: u64,
+        claimant: Option<u64>,
+        note: Option<&str>,
+    ) -> Result<Outcome, Error> {
+        Ok(self
+            .close_report_inner(id, who, claimant, note, None)
+            .await?
+            .0)
+    }
+    /// close a claimed report and add the audit for it, all or nothing.
+    /// the audit id is only there if the outcome is [`Outcome::Done`]
+    pub async fn close_report_with_action(
+        &self,
+        id: u32,
+        who: u64,
+        claimant: Option<u64>,
+        action: Action,
+    ) -> Result<(Outcome, Option<u32>), Error> {
+        self.close_report_inner(id, who, claimant, None, Some(action))
+            .await
+    }
+    async fn close_report_inner(
+        &self,
+        id: u32,
+        who: u64,
+        claimant: Option<u64>,
+        note: Option<&str>,
+        action: Option<Action>,
+    ) -> Result<(Outcome, Option<u32>), Error> {
+        let rid = id;
+        let id = id as i64;
+        let old = match self.current_status(id, &["claimed"]).await? {
+            Ok(s) => s,
+            Err(o) => return Ok((o, None)),
+        };
+        let c = claimant.map(|i| i.to_string());
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            "update Reports set report_status = 'closed', closed_at = strftime('%Y-%m-%dT%H:%M:%SZ', 'now') where id = ?1 and report_status = ?2 and (?3 is null or claimant = ?3)",
+            id,
+            old,
+            c
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok((outcome_of(&mut tx, id).await?, None));
+        }
+        record_event(&mut tx, id, Some(who), Some(&old), "closed", note).await?;
+        let aid = match action {
+            Some(a) => {
+                let aid = insert_action(&mut tx, a).await?;
+                enqueue(&mut tx, &SideEffect::PostAudit(aid)).await?;
+                enqueue(&mut tx, &SideEffect::UpdateReport(rid)).await?;
+                Some(aid)
+            }
+            None => None,
+        };
+        tx.commit().await?;
+        Ok((Outcome::Done, aid))
+    }
+    /// give a claimed report back to everyone. `who` is None when lurk-chan does it by itself.
+    /// if `claimant` is set, only if they are the one who claimed it
+    pub async fn unclaim_report(
+        &self,
+        id: u32,
+        who: Option<u64>,
+        claimant: Option<u64>,
+        note: Option<&str>,
+    ) -> Result<Outcome, Error> {
+        let id = id as i64;
+        let old = match self.current_status(id, &["claimed"]).await? {
+            Ok(s) => s,
+            Err(o) => return Ok(o),
+        };
+        let c = claimant.map(|i| i.to_string());
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            "update Reports set report_status = 'open', claimant = null where id = ?1 and report_status = ?2 and (?3 is null or claimant = ?3)",
+            id,
+            old,
+            c
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return outcome_of(&mut tx, id).await;
+        }
+        record_event(&mut tx, id, who, Some(&old), "open", note).await?;
+        tx.commit().await?;
+        Ok(Outcome::Done)
+    }
+    /// hand a claimed report over to someone else. if `claimant` is set, only if they are the one who claimed it
+    pub async fn reassign_report(
+        &self,
+        id: u32,
+        who: u64,
+        claimant: Option<u64>,
+        to: u64,
+        note: Option<&str>,
+    ) -> Result<Outcome, Error> {
+        let id = id as i64;
+        let old = match self.current_status(id, &["claimed"]).await? {
+            Ok(s) => s,
+            Err(o) => return Ok(o),
+        };
+        let c = claimant.map(|i| i.to_string());
+        let t = to.to_string();
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            "update Reports set claimant = ?1 where id = ?2 and report_status = ?3 and (?4 is null or claimant = ?4)",
+            t,
+            id,
+            old,
+            c
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return outcome_of(&mut tx, id).await;
+        }
+        record_event(&mut tx, id, Some(who), Some(&old), "claimed", note).await?;
+        tx.commit().await?;
+        Ok(Outcome::Done)
+    }
+    /// reopen a closed report, claimed by whoever reopened it
+    pub async fn reopen_report(&self, id: u32, who: u64) -> Result<Outcome, Error> {
+        let id = id as i64;
+        let old = match self.current_status(id, &["closed"]).await? {
+            Ok(s) => s,
+            Err(o) => return Ok(o),
+        };
+        let w = who.to_string();
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            "update Reports set report_status = 'claimed', claimant = ?1, closed_at = null where id = ?2 and report_status = ?3",
+            w,
+            id,
+            old
+        )
+        .execute(&mut *tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return outcome_of(&mut tx, id).await;
+        }
+        record_event(
+            &mut tx,
+            id,
+            Some(who),
+            Some(&old),
+            "claimed",
+            Some("Reopened"),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(Outcome::Done)
+    }
+    /// claimed reports where nothing has happened since `before` (a RFC 3339 timestamp)
+    pub async fn idle_claimed_reports(&self, guild: u64, before: &str) -> Result<Vec<u32>, Error> {
+        let g = guild.to_string();
+        let res = sqlx::query_scalar!(
+            r#"select R.id as "id!" from Reports R where R.report_status = 'claimed' and R.guild_id = ?
+            and (select max(julianday(E.time)) from ReportEvents E where E.report_id = R.id) < julianday(?)"#,
+            g,
+            before
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res.into_iter().map(|i| i as u32).collect())
+    }
+    /// every status change a report went through, oldest first
+    pub async fn report_events(&self, id: u32) -> Result<Vec<ReportEvent>, Error> {
+        let id = id as i64;
+        let res = sqlx::query!(
+            "select actor, time, old_status, new_status, note from ReportEvents where report_id = ? order by id",
+            id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        res.into_iter()
+            .map(|i| {
+                Ok(ReportEvent {
+                    actor: match i.actor {
+                        Some(a) => Some(a.parse()?),
+                        None => None,
+                    },
+                    time: i.time,
+                    old_status: match i.old_status {
+                        Some(s) => {
+                            Some(ReportStatus::from_db(&s).ok_or(Error::InvalidReportStatus(s))?)
+                        }
+                        None => None,
+                    },
+                    new_status: ReportStatus::from_db(&i.new_status)
+                        .ok_or(Error::InvalidReportStatus(i.new_status))?,
+                    note: i.note,
+                })
+            })
+            .collect()
+    }
+    /// replace `old` with `audit`. if `claimant` is set, only if it is their audit.
+    /// if someone else changed it since `old` was read nothing happens, and you get [`Outcome::Changed`]
     pub async fn edit_action(
         &self,
         id: u32,
+        old: &Action,
         audit: Action,
         now: String,
         who: u64,
+        claimant: Option<u64>,
+    ) -> Result<Outcome, Error> {
+        let mut tx = self.pool.begin().await?;
+        let outcome = edit_action_in(&mut tx, id, old, &audit, now, who, claimant).await?;
+        if outcome != Outcome::Done {
+            return Ok(outcome);
+        }
+        enqueue(&mut tx, &SideEffect::UpdateAudit(id)).await?;
+        tx.commit().await?;
+        Ok(Outcome::Done)
+    }
+    /// like [`Database::edit_action`], but the audit gets a new message (in whatever channel its server uses now)
+    /// and the old one is deleted
+    pub async fn move_action(
+        &self,
+        id: u32,
+        old: &Action,
+        audit: Action,
+        now: String,
+        who: u64,
+        claimant: Option<u64>,
+    ) -> Result<Outcome, Error> {
+        let mut tx = self.pool.begin().await?;
+        let outcome = edit_action_in(&mut tx, id, old, &audit, now, who, claimant).await?;
+        if outcome != Outcome::Done {
+            return Ok(outcome);
+        }
+        enqueue(&mut tx, &SideEffect::PostAudit(id)).await?;
+        let aid = id as i64;
+        let old_message = sqlx::query!(
+            "select channel, message from ActionMessages where action_id = ?",
+            aid
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(m) = old_message {
+            enqueue(
+                &mut tx,
+                &SideEffect::DeleteMessage {
+                    channel: m.channel.parse()?,
+                    message: m.message.parse()?,
+                },
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(Outcome::Done)
+    }
+    /// lease up to `limit` outbox items that are due, so nobody else tries them for a minute
+    pub async fn take_due_outbox(&self, limit: u32) -> Result<Vec<OutboxItem>, Error> {
+        let res = sqlx::query!(
+            r#"update Outbox set attempts = attempts + 1, next_attempt = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '+1 minutes')
+            where id in (select id from Outbox where julianday(next_attempt) <= julianday('now') order by id limit ?)
+            returning id as "id!", kind as "kind!", target, channel, message, attempts as "attempts!""#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut items = res
+            .into_iter()
+            .map(|i| {
+                let effect = match (i.kind.as_str(), i.target, i.channel, i.message) {
+                    ("post_audit", Some(t), _, _) => SideEffect::PostAudit(t as u32),
+                    ("update_report", Some(t), _, _) => SideEffect::UpdateReport(t as u32),
+                    ("update_audit", Some(t), _, _) => SideEffect::UpdateAudit(t as u32),
+                    ("delete_message", _, Some(c), Some(m)) => SideEffect::DeleteMessage {
+                        channel: c.parse()?,
+                        message: m.parse()?,
+                    },
+                    _ => return Err(Error::InvalidOutboxItem(i.id)),
+                };
+                Ok(OutboxItem {
+                    id: i.id,
+                    effect,
+                    attempts: i.attempts as u32,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        items.sort_by_key(|i| i.id);
+        Ok(items)
+    }
+    /// an outbox item worked. `posted` is the message a [`SideEffect::PostAudit`] made
+    pub async fn finish_outbox(
+        &self,
+        item: &OutboxItem,
+        posted: Option<(u64, u64)>,
     ) -> Result<(), Error> {
-        let old = self
-            .get_action_from_id(id)
-            .await?
-            .ok_or_else(|| Error::ActionNotFound(id))?;
-        let old_val = serde_json::to_value(&old).expect("should never fail");
-        let new_val = serde_json::to_value(&audit).expect("should never fail");
-        let diff = json_patch::diff(&old_val, &new_val);
-
-        let id = id as i64;
-        let old_str = serde_json::to_string(&old).expect("should never fail");
-        let new_str = serde_json::to_string(&audit).expect("should never fail");
-        let diff_str = serde_json::to_string(&diff).expect("should never fail");
-        let s = audit.server.to_string();
-        sqlx::query!("update Actions set target_id = ?, target_username = ?, offense = ?, action = ?, server = ? where id = ?", 
-                audit.target_id,
-                audit.target_username,
-                audit.offense,
-                audit.action,
-                s,
-                id).execute(&self.pool).await?;
-        let who_str = who.to_string();
+        let mut tx = self.pool.begin().await?;
+        if let (SideEffect::PostAudit(aid), Some((channel, message))) = (&item.effect, posted) {
+            let (a, b, c) = (*aid as i64, channel.to_string(), message.to_string());
+            sqlx::query!(
+                "insert or replace into ActionMessages(action_id, channel, message) values (?,?,?)",
+                a,
+                b,
+                c
+            )
+            .execute(&mut *tx)
+            .await?;
+            // its report links to it, which the report's message might have been updated without
+            let report = sqlx::query_scalar!("select report from Actions where id = ?", a)
+                .fetch_optional(&mut *tx)
+                .await?
+                .flatten();
+            if let Some(r) = report {
+                enqueue(&mut tx, &SideEffect::UpdateReport(r as u32)).await?;
+            }
+        }
+        sqlx::query!("delete from Outbox where id = ?", item.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    /// an outbox item didn't work, try again in `retry_in` seconds
+    pub async fn fail_outbox(&self, id: i64, error: &str, retry_in: u64) -> Result<(), Error> {
+        let modifier = format!("+{} seconds", retry_in);
         sqlx::query!(
-            "insert into AuditEdits(action_id, old, new, who, time, changes) values (?,?,?,?,?,?)",
-            id,
-            old_str,
-            new_str,
-            who_str,
-            now,
-            diff_str
+            "update Outbox set last_error = ?, next_attempt = strftime('%Y-%m-%dT%H:%M:%SZ', 'now', ?) where id = ?",
+            error,
+            modifier,
+            id
         )
         .execute(&self.pool)
         .await?;
         Ok(())
     }
-    pub async fn collect_user_info(&self, user: &str) -> Result<UserInfo, Error> {
+    /// how many outbox items are still waiting
+    pub async fn outbox_count(&self) -> Result<u32, Error> {
+        let res = sqlx::query_scalar!("select count(*) from Outbox")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(res as u32)
+    }
+    pub async fn collect_user_info(&self, user: &str, guild: u64) -> Result<UserInfo, Error> {
         const LIMIT: i32 = 10;
+        let g = guild.to_string();
         let (
             times_reported,
             preview_reported,
@@ -354,30 +1438,45 @@ impl Database {
             times_actioned,
             preview_actioned,
         ) = tokio::try_join!(
-            sqlx::query_scalar!("select count(*) from Reports where reported_id = ?", user)
-                .fetch_one(&self.pool),
+            sqlx::query_scalar!(
+                "select count(*) from Reports where reported_id = ? and guild_id = ?",
+                user,
+                g
+            )
+            .fetch_one(&self.pool),
             sqlx::query_as!(
                 DBReport,
-                "select * from Reports where reported_id = ? order by time desc limit ?",
+                "select * from Reports where reported_id = ? and guild_id = ? order by time desc limit ?",
                 user,
+                g,
                 LIMIT
             )
             .fetch_all(&self.pool),
-            sqlx::query_scalar!("select count(*) from Reports where reporter_id = ?", user)
-                .fetch_one(&self.pool),
+            sqlx::query_scalar!(
+                "select count(*) from Reports where reporter_id = ? and guild_id = ?",
+                user,
+                g
+            )
+            .fetch_one(&self.pool),
             sqlx::query_as!(
                 DBReport,
-                "select * from Reports where reporter_id = ? order by time desc limit ?",
+                "select * from Reports where reporter_id = ? and guild_id = ? order by time desc limit ?",
                 user,
+                g,
                 LIMIT
             )
             .fetch_all(&self.pool),
-            sqlx::query_scalar!("select count(*) from Actions where claimant = ?", user)
-                .fetch_one(&self.pool),
+            sqlx::query_scalar!(
+                "select count(*) from Actions where target_id = ? and guild_id = ?",
+                user,
+                g
+            )
+            .fetch_one(&self.pool),
             sqlx::query_as!(
                 DBAction,
-                "select * from Actions where claimant = ? order by id desc limit ?",
+                "select * from Actions where target_id = ? and guild_id = ? order by id desc limit ?",
                 user,
+                g,
                 LIMIT
             )
             .fetch_all(&self.pool),
@@ -400,26 +1499,243 @@ impl Database {
                 .collect(),
         })
     }
+    /// full text search over report reasons and reporter/reported names, best match first.
+    /// returns the total amount of hits along with the requested page
+    pub async fn search_reports(
+        &self,
+        guild: u64,
+        query: &str,
+        page: u32,
+        per_page: u32,
+    ) -> Result<(u32, Vec<SearchHit<Report>>), Error> {
+        let q = fts_query(query);
+        let g = guild.to_string();
+        // a page past the end is just empty
+        let offset = page.saturating_mul(per_page);
+        let (total, res) = tokio::try_join!(
+            sqlx::query_scalar!(
+                "select count(*) from ReportSearch S join Reports R on R.id = S.rowid where ReportSearch match ? and R.guild_id = ?",
+                q,
+                g
+            )
+            .fetch_one(&self.pool),
+            sqlx::query!(
+                r#"select R.id, R.reporter_id, R.reporter_name, R.reported_id, R.reported_name, R.report_reason, R.report_status, R.server, R.time, R.claimant, R.location, R.claimed_at, R.closed_at, R.guild_id,
+                snippet(ReportSearch, -1, '**', '**', '...', 12) as "snippet!: String"
+                from ReportSearch S join Reports R on R.id = S.rowid where ReportSearch match ? and R.guild_id = ? order by rank limit ? offset ?"#,
+                q,
+                g,
+                per_page,
+                offset
+            )
+            .fetch_all(&self.pool)
+        )?;
+        let hits = res
+            .into_iter()
+            .map(|i| {
+                let r = DBReport {
+                    id: Some(i.id),
+                    reporter_id: i.reporter_id,
+                    reporter_name: i.reporter_name,
+                    reported_id: i.reported_id,
+                    reported_name: i.reported_name,
+                    report_reason: i.report_reason,
+                    report_status: i.report_status,
+                    server: i.server,
+                    time: i.time,
+                    claimant: i.claimant,
+                    location: i.location,
+                    claimed_at: i.claimed_at,
+                    closed_at: i.closed_at,
+                    source_channel: None,
+                    source_message: None,
+                    guild_id: i.guild_id,
+                };
+                Ok(SearchHit {
+                    id: i.id as u32,
+                    item: r.into_report()?,
+                    snippet: i.snippet,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok((total as u32, hits))
+    }
+    /// full text search over audit offenses and actions, best match first.
+    /// returns the total amount of hits along with the requested page
+    pub async fn search_actions(
+        &self,
+        guild: u64,
+        query: &str,
+        page: u32,
+        per_page: u32,
+    ) -> Result<(u32, Vec<SearchHit<Action>>), Error> {
+        let q = fts_query(query);
+        let g = guild.to_string();
+        // a page past the end is just empty
+        let offset = page.saturating_mul(per_page);
+        let (total, res) = tokio::try_join!(
+            sqlx::query_scalar!(
+                "select count(*) from ActionsSearch S join Actions A on A.id = S.rowid where ActionsSearch match ? and A.guild_id = ?",
+                q,
+                g
+            )
+            .fetch_one(&self.pool),
+            sqlx::query!(
+                r#"select A.id, A.target_id, A.target_username, A.offense, A.action, A.server, A.claimant, A.report, A.created_at, A.guild_id,
+                A.punishment, A.punishment_secs, A.expires_at,
+                snippet(ActionsSearch, -1, '**', '**', '...', 12) as "snippet!: String"
+                from ActionsSearch S join Actions A on A.id = S.rowid where ActionsSearch match ? and A.guild_id = ? order by rank limit ? offset ?"#,
+                q,
+                g,
+                per_page,
+                offset
+            )
+            .fetch_all(&self.pool)
+        )?;
+        let hits = res
+            .into_iter()
+            .map(|i| {
+                let a = DBAction {
+                    id: Some(i.id),
+                    target_id: i.target_id,
+                    target_username: i.target_username,
+                    offense: i.offense,
+                    action: i.action,
+                    server: i.server,
+                    claimant: i.claimant,
+                    report: i.report,
+                    created_at: i.created_at,
+                    guild_id: i.guild_id,
+                    punishment: i.punishment,
+                    punishment_secs: i.punishment_secs,
+                    expires_at: i.expires_at,
+                };
+                Ok(SearchHit {
+                    id: i.id as u32,
+                    item: a.try_into()?,
+                    snippet: i.snippet,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok((total as u32, hits))
+    }
+    /// seconds between each claimed report coming in and being claimed/closed,
+    /// along with where it came from and who claimed it
+    async fn response_durations(
+        &self,
+        guild: u64,
+    ) -> Result<Vec<(String, Option<String>, Option<f64>, Option<f64>)>, Error> {
+        let g = guild.to_string();
+        let res = sqlx::query!(
+            r#"select location, claimant,
+            (julianday(claimed_at) - julianday(time)) * 86400.0 as "to_claim?: f64",
+            (julianday(closed_at) - julianday(time)) * 86400.0 as "to_close?: f64"
+            from Reports where claimed_at is not null and guild_id = ?"#,
+            g
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res
+            .into_iter()
+            .map(|i| (i.location, i.claimant, i.to_claim, i.to_close))
+            .collect())
+    }
+    /// mean and median time to claim/close, per location
+    pub async fn response_times_by_location(
+        &self,
+        guild: u64,
+    ) -> Result<Vec<(Location, ResponseStats)>, Error> {
+        let mut grouped: BTreeMap<String, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+        for (location, _, to_claim, to_close) in self.response_durations(guild).await? {
+            let e = grouped.entry(location).or_default();
+            e.0.extend(to_claim);
+            e.1.extend(to_close);
+        }
+        grouped
+            .into_iter()
+            .map(|(l, (claim, close))| {
+                Ok((Location::from_str(&l)?, ResponseStats::new(claim, close)))
+            })
+            .collect()
+    }
+    /// mean and median time to claim/close, per staff member. busiest staff first
+    pub async fn response_times_by_staff(
+        &self,
+        guild: u64,
+    ) -> Result<Vec<(u64, ResponseStats)>, Error> {
+        let mut grouped: BTreeMap<String, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+        for (_, claimant, to_claim, to_close) in self.response_durations(guild).await? {
+            let Some(claimant) = claimant else { continue };
+            let e = grouped.entry(claimant).or_default();
+            e.0.extend(to_claim);
+            e.1.extend(to_close);
+        }
+        let mut res = grouped
+            .into_iter()
+            .map(|(c, (claim, close))| Ok((c.parse()?, ResponseStats::new(claim, close))))
+            .collect::<Result<Vec<_>, Error>>()?;
+        res.sort_by_key(|i| std::cmp::Reverse(i.1.to_claim.count));
+        Ok(res)
+    }
     pub async fn total_report_count(&self) -> Result<u32, Error> {
         let res = sqlx::query_scalar!("select count(*) from Reports")
             .fetch_one(&self.pool)
             .await?;
         Ok(res as u32)
     }
+    /// how many audits there are of each [`Punishment::to_db`] kind (and `other`), most first.
+    /// every guild if `guild` is None
+    pub async fn punishment_counts(&self, guild: Option<u64>) -> Result<Vec<(String, u32)>, Error> {
+        let g = guild.map(|g| g.to_string());
+        let res = sqlx::query!(
+            r#"select coalesce(punishment, 'other') as "kind!: String", count(*) as "count!: i64" from Actions
+            where (?1 is null or guild_id = ?1)
+            group by 1 order by 2 desc"#,
+            g
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(res.into_iter().map(|r| (r.kind, r.count as u32)).collect())
+    }
     pub async fn total_action_count(&self) -> Result<u32, Error> {
         let res= sqlx::query_scalar!("select count(*) from Actions")
             .fetch_one(&self.pool)
             .await?;
         Ok(res as u32)
     }
-    pub async fn get_report_count_by_status(&self, status: ReportStatus) -> Result<u32, Error> {
+    pub async fn get_report_count_by_status(
+        &self,
+        status: ReportStatus,
+        guild: u64,
+    ) -> Result<u32, Error> {
         let s = status.to_db();
-        let res =
-            sqlx::query_scalar!("select count(*) from Reports where report_status = ?", s)
-                .fetch_one(&self.pool)
-                .await?;
+        let g = guild.to_string();
+        let res = sqlx::query_scalar!(
+            "select count(*) from Reports where report_status = ? and guild_id = ?",
+            s,
+            g
+        )
+        .fetch_one(&self.pool)
+        .await?;
         Ok(res as u32)
     }
+    /// how many reports there are with each status, in every guild
+    pub async fn report_count_by_status(&self) -> Result<Vec<(ReportStatus, u32)>, Error> {
+        let res = sqlx::query!(
+            r#"select report_status, count(*) as "count!: i64" from Reports group by report_status order by report_status"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        res.into_iter()
+            .map(|i| {
+                Ok((
+                    ReportStatus::from_db(&i.report_status)
+                        .ok_or(Error::InvalidReportStatus(i.report_status))?,
+                    i.count as u32,
+                ))
+            })
+            .collect()
+    }
     pub async fn get_report_message_count(&self) -> Result<u32, Error> {
         let res = sqlx::query_scalar!("select count(*) from ReportMessages")
             .fetch_one(&self.pool)
@@ -466,6 +1782,352 @@ pub struct UserInfo {
     pub preview_actioned: Vec<(u32, Action)>,
 }
 
+/// where a guild wants things posted. channel ids, None for not set up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GuildSettings {
+    pub guild_id: u64,
+    pub sl_audit: Option<u64>,
+    pub discord_audit: Option<u64>,
+    pub discord_reports: Option<u64>,
+    pub stats: Option<u64>,
+}
+
+impl GuildSettings {
+    /// where audits for `location` go
+    pub fn audit_channel(&self, location: &Location) -> Option<u64> {
+        match location {
+            Location::SL => self.sl_audit,
+            Location::Discord => self.discord_audit,
+        }
+    }
+}
+
+fn parse_opt(i: Option<String>) -> Result<Option<u64>, Error> {
+    Ok(match i {
+        Some(i) => Some(i.parse()?),
+        None => None,
+    })
+}
+
+/// something that has to happen on discord after a database change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SideEffect {
+    /// post the message for an audit (and remember it)
+    PostAudit(u32),
+    /// bring a report's message up to date
+    UpdateReport(u32),
+    /// bring an audit's message up to date
+    UpdateAudit(u32),
+    /// delete a message that was replaced
+    DeleteMessage { channel: u64, message: u64 },
+}
+
+/// a [`SideEffect`] waiting in the outbox
+#[derive(Debug, Clone)]
+pub struct OutboxItem {
+    pub id: i64,
+    pub effect: SideEffect,
+    /// including the one that's happening now
+    pub attempts: u32,
+}
+
+/// what happened when something tried to change a report or audit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// it worked
+    Done,
+    /// there's nothing with that id
+    NotFound,
+    /// it's claimed by (or the audit belongs to) someone else
+    BelongsTo(u64),
+    /// the report isn't in a status where that makes sense anymore
+    WrongStatus(ReportStatus),
+    /// someone else edited it in the meantime
+    Changed,
+}
+
+/// a single change to the status of a report
+pub struct ReportEvent {
+    /// who did it. None if lurk-chan did it by itself
+    pub actor: Option<u64>,
+    pub time: String,
+    /// None if this is when the report came in
+    pub old_status: Option<ReportStatus>,
+    pub new_status: ReportStatus,
+    pub note: Option<String>,
+}
+
+async fn status_of(conn: &mut SqliteConnection, id: i64) -> Result<Option<String>, Error> {
+    Ok(
+        sqlx::query_scalar!("select report_status from Reports where id = ?", id)
+            .fetch_optional(conn)
+            .await?,
+    )
+}
+
+async fn insert_action(conn: &mut SqliteConnection, action: Action) -> Result<u32, Error> {
+    let a = DBAction::from(action);
+    let res =
+        sqlx::query!(
+            "insert into Actions(target_id, target_username, offense, action, server, claimant, report, created_at, guild_id, punishment, punishment_secs) values (?,?,?,?,?,?,?,strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),?,?,?)", 
+            a.target_id,
+            a.target_username,
+            a.offense,
+            a.action,
+            a.server,
+            a.claimant,
+            a.report,
+            a.guild_id,
+            a.punishment,
+            a.punishment_secs
+        ).execute(conn).await?;
+    Ok(res.last_insert_rowid() as u32)
+}
+
+async fn edit_action_in(
+    conn: &mut SqliteConnection,
+    id: u32,
+    old: &Action,
+    audit: &Action,
+    now: String,
+    who: u64,
+    claimant: Option<u64>,
+) -> Result<Outcome, Error> {
+    let old_val = serde_json::to_value(old).expect("should never fail");
+    let new_val = serde_json::to_value(audit).expect("should never fail");
+    let diff = json_patch::diff(&old_val, &new_val);
+
+    let id = id as i64;
+    let old_str = serde_json::to_string(old).expect("should never fail");
+    let new_str = serde_json::to_string(audit).expect("should never fail");
+    let diff_str = serde_json::to_string(&diff).expect("should never fail");
+    let s = audit.server.to_string();
+    let old_s = old.server.to_string();
+    let c = claimant.map(|i| i.to_string());
+    let (p, p_secs) = punishment_to_db(audit.punishment);
+    let (old_p, old_p_secs) = punishment_to_db(old.punishment);
+    let res = sqlx::query!(
+        "update Actions set target_id = ?1, target_username = ?2, offense = ?3, action = ?4, server = ?5,
+        punishment = ?13, punishment_secs = ?14
+        where id = ?6 and target_id = ?7 and target_username = ?8 and offense = ?9 and action = ?10 and server = ?11
+        and (?12 is null or claimant = ?12)
+        and coalesce(punishment, 'other') = ?15 and punishment_secs is ?16",
+        audit.target_id,
+        audit.target_username,
+        audit.offense,
+        audit.action,
+        s,
+        id,
+        old.target_id,
+        old.target_username,
+        old.offense,
+        old.action,
+        old_s,
+        c,
+        p,
+        p_secs,
+        old_p,
+        old_p_secs
+    )
+    .execute(&mut *conn)
+    .await?;
+    if res.rows_affected() == 0 {
+        let owner = sqlx::query_scalar!("select claimant from Actions where id = ?", id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        return Ok(match (owner, c) {
+            (None, _) => Outcome::NotFound,
+            (Some(o), Some(c)) if o != c => Outcome::BelongsTo(o.parse()?),
+            _ => Outcome::Changed,
+        });
+    }
+    let who_str = who.to_string();
+    sqlx::query!(
+        "insert into AuditEdits(action_id, old, new, who, time, changes) values (?,?,?,?,?,?)",
+        id,
+        old_str,
+        new_str,
+        who_str,
+        now,
+        diff_str
+    )
+    .execute(conn)
+    .await?;
+    Ok(Outcome::Done)
+}
+
+async fn enqueue(conn: &mut SqliteConnection, effect: &SideEffect) -> Result<(), Error> {
+    let (kind, target, channel, message) = match effect {
+        SideEffect::PostAudit(id) => ("post_audit", Some(*id as i64), None, None),
+        SideEffect::UpdateReport(id) => ("update_report", Some(*id as i64), None, None),
+        SideEffect::UpdateAudit(id) => ("update_audit", Some(*id as i64), None, None),
+        SideEffect::DeleteMessage { channel, message } => (
+            "delete_message",
+            None,
+            Some(channel.to_string()),
+            Some(message.to_string()),
+        ),
+    };
+    sqlx::query!(
+        "insert into Outbox(kind, target, channel, message) values (?,?,?,?)",
+        kind,
+        target,
+        channel,
+        message
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// why a guarded update on a report didn't go through
+async fn outcome_of(conn: &mut SqliteConnection, id: i64) -> Result<Outcome, Error> {
+    let Some(r) = sqlx::query!(
+        "select report_status, claimant from Reports where id = ?",
+        id
+    )
+    .fetch_optional(conn)
+    .await?
+    else {
+        return Ok(Outcome::NotFound);
+    };
+    let status = ReportStatus::from_db(&r.report_status)
+        .ok_or(Error::InvalidReportStatus(r.report_status))?;
+    Ok(match (status, r.claimant) {
+        (ReportStatus::Claimed, Some(c)) => Outcome::BelongsTo(c.parse()?),
+        (status, _) => Outcome::WrongStatus(status),
+    })
+}
+
+async fn was_imported(conn: &mut SqliteConnection, key: &str) -> Result<bool, Error> {
+    Ok(
+        sqlx::query_scalar!("select count(*) from Imports where key = ?", key)
+            .fetch_one(conn)
+            .await?
+            > 0,
+    )
+}
+
+async fn record_import(
+    conn: &mut SqliteConnection,
+    key: &str,
+    kind: &str,
+    row_id: i64,
+    source: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "insert into Imports(key, kind, row_id, source) values (?,?,?,?)",
+        key,
+        kind,
+        row_id,
+        source
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn record_event(
+    conn: &mut SqliteConnection,
+    report_id: i64,
+    actor: Option<u64>,
+    old_status: Option<&str>,
+    new_status: &str,
+    note: Option<&str>,
+) -> Result<(), Error> {
+    let actor = actor.map(|i| i.to_string());
+    sqlx::query!(
+        "insert into ReportEvents(report_id, actor, old_status, new_status, note) values (?,?,?,?,?)",
+        report_id,
+        actor,
+        old_status,
+        new_status,
+        note
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// mean/median of a bunch of durations, in seconds
+#[derive(Debug, Clone, Default)]
+pub struct ResponseTimes {
+    pub count: u32,
+    pub mean: f64,
+    pub median: f64,
+}
+
+impl ResponseTimes {
+    fn from_durations(mut d: Vec<f64>) -> Self {
+        if d.is_empty() {
+            return Self::default();
+        }
+        d.sort_by(f64::total_cmp);
+        // the middle value, or the average of the two middle values
+        let median = (d[(d.len() - 1) / 2] + d[d.len() / 2]) / 2.0;
+        Self {
+            count: d.len() as u32,
+            mean: d.iter().sum::<f64>() / d.len() as f64,
+            median,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResponseStats {
+    /// time from a report coming in to it being claimed
+    pub to_claim: ResponseTimes,
+    /// time from a report coming in to it being closed
+    pub to_close: ResponseTimes,
+}
+
+impl ResponseStats {
+    fn new(claim: Vec<f64>, close: Vec<f64>) -> Self {
+        Self {
+            to_claim: ResponseTimes::from_durations(claim),
+            to_close: ResponseTimes::from_durations(close),
+        }
+    }
+}
+
+/// an audit, and when its punishment runs out
+pub struct ActivePunishment {
+    pub id: u32,
+    pub action: Action,
+    /// RFC 3339. None if it never does, like a permanent ban
+    pub expires_at: Option<String>,
+}
+
+impl TryFrom<DBAction> for ActivePunishment {
+    type Error = Error;
+    fn try_from(a: DBAction) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: a.id.expect("from the database") as u32,
+            expires_at: a.expires_at.clone(),
+            action: a.try_into()?,
+        })
+    }
+}
+
+/// a single full text search result
+pub struct SearchHit<T> {
+    /// the id of the report/action
+    pub id: u32,
+    pub item: T,
+    /// the matching part of the text, with the matches in **bold**
+    pub snippet: String,
+}
+
+/// turn user input into a fts5 query that can't be a syntax error.
+/// every word is quoted, so `ghost ban` matches rows containing both words
+fn fts_query(input: &str) -> String {
+    input
+        .split_whitespace()
+        .map(|w| format!("\"{}\"", w.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /*id integer primary key,
 reporter_id text not null,
 reporter_name text not null,
@@ -490,6 +2152,11 @@ struct DBReport {
     time: String,
     claimant: Option<String>,
     location: String,
+    claimed_at: Option<String>,
+    closed_at: Option<String>,
+    source_channel: Option<String>,
+    source_message: Option<String>,
+    guild_id: Option<String>,
 }
 
 impl DBReport {
@@ -509,6 +2176,12 @@ impl DBReport {
                 None => None,
             },
             location: Location::from_str(self.location.as_str())?,
+            claimed_at: self.claimed_at,
+            closed_at: self.closed_at,
+            guild_id: match self.guild_id {
+                Some(i) => Some(i.parse()?),
+                None => None,
+            },
         })
     }
     fn from_report(r: Report) -> Self {
@@ -524,6 +2197,11 @@ impl DBReport {
             time: r.time,
             claimant: r.claimant.map(|i| i.to_string()),
             location: r.location.to_string(),
+            claimed_at: r.claimed_at,
+            closed_at: r.closed_at,
+            source_channel: None,
+            source_message: None,
+            guild_id: r.guild_id.map(|i| i.to_string()),
         }
     }
 }
@@ -537,10 +2215,17 @@ struct DBAction {
     pub server: String,
     pub claimant: String,
     pub report: Option<i64>,
+    pub created_at: Option<String>,
+    pub guild_id: Option<String>,
+    pub punishment: Option<String>,
+    pub punishment_secs: Option<i64>,
+    /// worked out by the database
+    pub expires_at: Option<String>,
 }
 
 impl From<Action> for DBAction {
     fn from(value: Action) -> Self {
+        let (punishment, punishment_secs) = punishment_to_db(value.punishment);
         Self {
             id: None,
             report: value.report.map(|i| i as i64),
@@ -550,9 +2235,23 @@ impl From<Action> for DBAction {
             offense: value.offense,
             action: value.action,
             claimant: value.claimant.to_string(),
+            created_at: value.created_at,
+            guild_id: value.guild_id.map(|i| i.to_string()),
+            punishment: Some(punishment),
+            punishment_secs,
+            expires_at: None,
         }
     }
 }
+
+/// what goes in `punishment` and `punishment_secs`. audits without one are `other`,
+/// so they aren't mistaken for old ones that haven't been classified
+fn punishment_to_db(p: Option<Punishment>) -> (String, Option<i64>) {
+    match p.map(|p| p.to_db()) {
+        Some((kind, secs)) => (kind.to_string(), secs),
+        None => ("other".to_string(), None),
+    }
+}
 impl TryInto<Action> for DBAction {
     type Error = Error;
     fn try_into(self) -> Result<Action, Self::Error> {
@@ -564,6 +2263,14 @@ impl TryInto<Action> for DBAction {
             action: self.action,
             claimant: self.claimant.parse()?,
             server: Location::from_str(&self.server)?,
+            created_at: self.created_at,
+            punishment: self
+                .punishment
+                .and_then(|k| Punishment::from_db(&k, self.punishment_secs)),
+            guild_id: match self.guild_id {
+                Some(i) => Some(i.parse()?),
+                None => None,
+            },
         })
     }
 }
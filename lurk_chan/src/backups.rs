@@ -0,0 +1,264 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context as _};
+use chrono::{DateTime, Utc};
+use database::{Database, BACKUP_DIR, DB_FILE};
+use tracing::{error, info, warn};
+
+use crate::{BackupConfig, LurkChan};
+
+/// a backup waiting to replace the database the next time lurk-chan starts
+pub const STAGED_RESTORE: &str = "lurk_chan.db.restore";
+/// what compressed backups end with
+const ZSTD: &str = ".zst";
+
+/// a file in `backups`
+pub struct Backup {
+    pub name: String,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+}
+
+/// back the database up into `backups`, check it, and prune old ones with `[backup]`
+pub async fn create(lc: &LurkChan) -> anyhow::Result<PathBuf> {
+    info!("Backing up DB");
+    let config = lc.config.get().backup.clone();
+    tokio::fs::create_dir_all(BACKUP_DIR)
+        .await
+        .context("Failed to create backups directory")?;
+    let path = PathBuf::from(BACKUP_DIR).join(format!("backup_{}.db", Utc::now().timestamp()));
+    lc.db.backup_to(path.clone()).await?;
+    if let Err(e) = check_file(&path).await {
+        // a broken backup is worse than none, it'd get restored one day
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e.context("The backup is broken, deleted it"));
+    }
+    let path = if config.compress {
+        tokio::task::spawn_blocking(move || compress(&path)).await??
+    } else {
+        path
+    };
+    prune(&config).await;
+    info!("DB backed up to {}", path.display());
+    Ok(path)
+}
+
+/// fails if the database is corrupt or has rows pointing at things that don't exist
+pub async fn check(db: &Database) -> anyhow::Result<()> {
+    db.integrety_check()
+        .await
+        .context("Integrity check failed")?;
+    let broken = db.foreign_key_check().await?;
+    if broken > 0 {
+        bail!("{} row(s) point at things that don't exist", broken);
+    }
+    Ok(())
+}
+
+/// [`check`] a database that isn't open
+async fn check_file(path: &Path) -> anyhow::Result<()> {
+    let db = Database::open(path)
+        .await
+        .with_context(|| format!("Can't open {}", path.display()))?;
+    let checked = check(&db).await;
+    db.close().await?;
+    checked
+}
+
+/// swap `x.db` for `x.db.zst`
+fn compress(path: &Path) -> anyhow::Result<PathBuf> {
+    let mut to = path.as_os_str().to_owned();
+    to.push(ZSTD);
+    let to = PathBuf::from(to);
+    zstd::stream::copy_encode(File::open(path)?, File::create(&to)?, 0)
+        .context("Failed to compress the backup")?;
+    std::fs::remove_file(path)?;
+    Ok(to)
+}
+
+/// every backup, newest first
+pub fn list() -> anyhow::Result<Vec<Backup>> {
+    let dir = match std::fs::read_dir(BACKUP_DIR) {
+        Ok(d) => d,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).context("Failed to read the backups directory"),
+    };
+    let mut backups = vec![];
+    for entry in dir {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if !meta.is_file() || !(name.ends_with(".db") || name.ends_with(".db.zst")) {
+            continue;
+        }
+        backups.push(Backup {
+            name,
+            size: meta.len(),
+            modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH).into(),
+        });
+    }
+    backups.sort_by_key(|b| Reverse(b.modified));
+    Ok(backups)
+}
+
+/// when a scheduled backup was taken, from its name. None for anything else in `backups`,
+/// like the ones from before migrating, which are never pruned
+fn scheduled_time(name: &str) -> Option<i64> {
+    name.strip_prefix("backup_")?
+        .trim_end_matches(ZSTD)
+        .strip_suffix(".db")?
+        .parse()
+        .ok()
+}
+
+/// delete the scheduled backups `[backup]` says not to keep
+async fn prune(config: &BackupConfig) {
+    let backups = match list() {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Failed to list backups, not pruning them: {:#}", e);
+            return;
+        }
+    };
+    let scheduled = backups
+        .iter()
+        .filter_map(|b| Some((scheduled_time(&b.name)?, b.name.as_str())))
+        .collect();
+    for name in to_prune(config, scheduled) {
+        match tokio::fs::remove_file(Path::new(BACKUP_DIR).join(name)).await {
+            Ok(()) => info!("Removed old backup {}", name),
+            Err(e) => error!("Failed to remove old backup {}: {}", name, e),
+        }
+    }
+}
+
+/// keep the newest backup from each of the last `hourly` hours, `daily` days and `weekly` weeks
+/// (that have one), and get rid of the rest. the newest one is always kept
+fn to_prune<'a>(config: &BackupConfig, mut backups: Vec<(i64, &'a str)>) -> Vec<&'a str> {
+    backups.sort_by(|a, b| b.cmp(a));
+    let mut keep = HashSet::new();
+    if let Some((_, newest)) = backups.first() {
+        keep.insert(*newest);
+    }
+    for (count, secs) in [
+        (config.hourly, 60 * 60),
+        (config.daily, 24 * 60 * 60),
+        (config.weekly, 7 * 24 * 60 * 60),
+    ] {
+        let mut periods = HashSet::new();
+        for (time, name) in &backups {
+            if periods.len() == count {
+                break;
+            }
+            if periods.insert(time.div_euclid(secs)) {
+                keep.insert(*name);
+            }
+        }
+    }
+    backups
+        .into_iter()
+        .map(|(_, name)| name)
+        .filter(|name| !keep.contains(name))
+        .collect()
+}
+
+/// copy (or decompress) a backup next to the database and check it, so it's swapped in by
+/// [`apply_staged`] the next time lurk-chan starts
+pub async fn stage(path: &Path) -> anyhow::Result<()> {
+    if !path.is_file() {
+        bail!("There's no backup at {}", path.display());
+    }
+    let tmp = format!("{}.tmp", STAGED_RESTORE);
+    let from = path.to_owned();
+    let to = tmp.clone();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        if from.extension().is_some_and(|e| *e == ZSTD[1..]) {
+            zstd::stream::copy_decode(File::open(&from)?, File::create(&to)?)
+                .context("Failed to decompress the backup")?;
+        } else {
+            std::fs::copy(&from, &to)?;
+        }
+        Ok(())
+    })
+    .await??;
+    if let Err(e) = check_file(Path::new(&tmp)).await {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(e.context("Not restoring a broken backup"));
+    }
+    tokio::fs::rename(&tmp, STAGED_RESTORE).await?;
+    info!(
+        "Staged {} to be restored the next time lurk-chan starts",
+        path.display()
+    );
+    Ok(())
+}
+
+/// put a staged backup in place of the database, and the database aside. run it before
+/// anything opens the database. returns where the old database went
+pub async fn apply_staged() -> anyhow::Result<Option<PathBuf>> {
+    let staged = Path::new(STAGED_RESTORE);
+    if !staged.exists() {
+        return Ok(None);
+    }
+    // it was checked when it was staged, but it's been sitting around since
+    if let Err(e) = check_file(staged).await {
+        warn!("Leaving {} where it is", STAGED_RESTORE);
+        return Err(e.context("The staged backup is broken, not restoring it"));
+    }
+    let current = Path::new(DB_FILE);
+    let aside = PathBuf::from(format!(
+        "{}.{}.old",
+        DB_FILE,
+        Utc::now().format("%Y%m%d%H%M%S")
+    ));
+    if current.exists() {
+        // flush the WAL so nothing is left behind in it
+        Database::open(DB_FILE).await?.close().await?;
+        std::fs::rename(current, &aside)?;
+    }
+    // left over from the old database, they'd be applied to the new one
+    for leftover in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", DB_FILE, leftover));
+    }
+    std::fs::rename(staged, current)?;
+    // not migrated here, so a backup from before a bad migration can be restored for an older lurk-chan
+    info!("Restored the staged backup");
+    Ok(aside.exists().then_some(aside))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_prune;
+    use crate::BackupConfig;
+
+    #[test]
+    fn keeps_one_per_period() {
+        let config = BackupConfig {
+            hourly: 2,
+            daily: 2,
+            weekly: 0,
+            compress: false,
+        };
+        const HOUR: i64 = 60 * 60;
+        const DAY: i64 = 24 * HOUR;
+        let backups = vec![
+            (10 * DAY + 2 * HOUR + 30, "a"),
+            (10 * DAY + 2 * HOUR, "b"),
+            (10 * DAY + HOUR, "c"),
+            (10 * DAY, "d"),
+            (9 * DAY + 5 * HOUR, "e"),
+            (9 * DAY, "f"),
+            (8 * DAY, "g"),
+        ];
+        let mut pruned = to_prune(&config, backups);
+        pruned.sort();
+        // a and c for the hours, a and e for the days
+        assert_eq!(pruned, ["b", "d", "f", "g"]);
+    }
+}
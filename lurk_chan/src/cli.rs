@@ -0,0 +1,326 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _};
+use chrono::NaiveDate;
+use clap::{Args, Subcommand, ValueEnum};
+use common::{Location, Punishment, ReportStatus};
+use database::{Database, ExportFilter, ExportFormat, ExportTable, BACKUP_DIR, DB_FILE};
+
+use crate::{backups, import, read_config};
+
+/// things to do instead of running the bot. none of them connect to discord
+#[derive(Subcommand, Debug)]
+pub enum Cli {
+    /// Bring the database up to date and exit
+    Migrate,
+    /// Undo migrations newer than <version> with their .down.sql scripts. Lists them without a version
+    Rollback { version: Option<i64> },
+    /// Check config.toml (and the environment) for mistakes
+    CheckConfig,
+    /// Copy the database to a new file
+    Backup { path: PathBuf },
+    /// Replace the database with a backup (compressed or not). Stop the bot first!
+    Restore { path: PathBuf },
+    /// Check the database for corruption and broken links between tables
+    Integrity,
+    /// Write reports, audits or audit edits as CSV or JSON lines
+    Export(ExportArgs),
+    /// Print how much is in the database
+    Stats,
+    /// Add reports or audits from a spreadsheet (CSV/JSON) or SL's ban files. Things imported before are skipped
+    Import {
+        what: import::Kind,
+        path: PathBuf,
+        /// The server they're from (default: the only one lurk-chan is in)
+        #[arg(long)]
+        guild: Option<u64>,
+        /// Rename a column before reading it, like --map "Steam ID=reported_id". Can be given more than once
+        #[arg(long, value_parser = parse_map)]
+        map: Vec<(String, String)>,
+        /// Only say what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// what to export and where to, for `export` here and in the console
+#[derive(Args, Debug, Clone)]
+pub struct ExportArgs {
+    what: Table,
+    #[arg(short, long, default_value = "jsonl")]
+    format: Format,
+    /// Only rows from this day on (YYYY-MM-DD)
+    #[arg(long)]
+    since: Option<NaiveDate>,
+    /// Only rows up to and including this day (YYYY-MM-DD)
+    #[arg(long)]
+    until: Option<NaiveDate>,
+    #[arg(long)]
+    location: Option<Loc>,
+    /// Only reports with this status
+    #[arg(long)]
+    status: Option<Status>,
+    /// Only things claimed or done by this staff member (discord id)
+    #[arg(long)]
+    claimant: Option<u64>,
+    /// Only things about this player (steam/discord id)
+    #[arg(long)]
+    target: Option<String>,
+    /// Only this server (default: all of them)
+    #[arg(long)]
+    guild: Option<u64>,
+    /// Where to write it
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Table {
+    Reports,
+    Audits,
+    Edits,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Format {
+    Csv,
+    Jsonl,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Loc {
+    Sl,
+    Discord,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Status {
+    Open,
+    Claimed,
+    Closed,
+    Expired,
+}
+
+impl ExportArgs {
+    /// where it goes if there's no `--out`, like `exports/reports_1700000000.csv`
+    pub fn default_path(&self) -> PathBuf {
+        PathBuf::from("exports").join(format!(
+            "{}_{}.{}",
+            format!("{:?}", self.what).to_lowercase(),
+            chrono::Utc::now().timestamp(),
+            self.format().extension()
+        ))
+    }
+    fn format(&self) -> ExportFormat {
+        match self.format {
+            Format::Csv => ExportFormat::Csv,
+            Format::Jsonl => ExportFormat::Jsonl,
+        }
+    }
+    fn filter(&self) -> ExportFilter {
+        let day = |d: NaiveDate| {
+            d.and_hms_opt(0, 0, 0)
+                .map(|d| lurk_chan::db_time(d.and_utc()))
+        };
+        ExportFilter {
+            guild: self.guild,
+            since: self.since.and_then(day),
+            // inclusive, so up to the start of the next day
+            until: self.until.and_then(|d| d.succ_opt()).and_then(day),
+            location: self.location.map(|l| match l {
+                Loc::Sl => Location::SL,
+                Loc::Discord => Location::Discord,
+            }),
+            status: self.status.map(|s| match s {
+                Status::Open => ReportStatus::Open,
+                Status::Claimed => ReportStatus::Claimed,
+                Status::Closed => ReportStatus::Closed,
+                Status::Expired => ReportStatus::Expired,
+            }),
+            claimant: self.claimant,
+            target: self.target.clone(),
+        }
+    }
+    /// write the rows to `out`, returning how many there were
+    pub async fn export(&self, db: &Database, out: &mut impl Write) -> anyhow::Result<usize> {
+        let table = match self.what {
+            Table::Reports => ExportTable::Reports,
+            Table::Audits => ExportTable::Audits,
+            Table::Edits => ExportTable::Edits,
+        };
+        Ok(db.export(table, self.format(), &self.filter(), out).await?)
+    }
+}
+
+pub async fn run(cli: Cli) -> anyhow::Result<()> {
+    match cli {
+        Cli::Migrate => {
+            if !Path::new(DB_FILE).exists() {
+                Database::new().await?;
+                println!("Created a new database");
+                return Ok(());
+            }
+            let db = Database::open(DB_FILE).await?;
+            let pending = db.pending_migrations().await?;
+            if pending.is_empty() {
+                println!("Already up to date");
+                return Ok(());
+            }
+            if let Some(path) = db.pre_migration_backup().await? {
+                println!("Backed up to {}", path.display());
+            }
+            db.migrate().await?;
+            for m in pending {
+                println!("Applied {}", m);
+            }
+        }
+        Cli::Rollback { version: None } => {
+            let db = Database::open(DB_FILE).await?;
+            println!("Applied migrations, oldest first:");
+            for (version, description) in db.applied_migrations().await? {
+                println!("{} {}", version, description);
+            }
+            println!("Run `rollback <version>` to undo everything after <version>");
+        }
+        Cli::Rollback {
+            version: Some(version),
+        } => {
+            let db = Database::open(DB_FILE).await?;
+            let path = PathBuf::from(BACKUP_DIR).join(format!(
+                "pre-rollback_{}.db",
+                chrono::Utc::now().timestamp()
+            ));
+            std::fs::create_dir_all(BACKUP_DIR)?;
+            db.backup_to(path.clone())
+                .await
+                .context("Failed to back up, not rolling back")?;
+            println!("Backed up to {}", path.display());
+            let undone = db.rollback(version).await?;
+            if undone.is_empty() {
+                println!("Nothing newer than {} to undo", version);
+            }
+            for m in undone {
+                println!("Undid {}", m);
+            }
+        }
+        Cli::CheckConfig => {
+            let config = read_config()?;
+            if config.permissions.is_none() {
+                println!("Warning: no [permissions], everyone is a Trial Mod and only the bot's owner can do more");
+            }
+            println!("Config is OK");
+        }
+        Cli::Backup { path } => {
+            if path.exists() {
+                bail!("{} already exists", path.display());
+            }
+            existing_db().await?.backup_to(path.clone()).await?;
+            println!("Backed up to {}", path.display());
+        }
+        Cli::Restore { path } => restore(&path).await?,
+        Cli::Integrity => integrity(&existing_db().await?).await?,
+        Cli::Export(args) => {
+            let db = current_db().await?;
+            let Some(path) = &args.out else {
+                let mut out = std::io::stdout().lock();
+                args.export(&db, &mut out).await?;
+                out.flush()?;
+                return Ok(());
+            };
+            let mut out = std::io::BufWriter::new(
+                std::fs::File::create(path)
+                    .with_context(|| format!("Can't create {}", path.display()))?,
+            );
+            let count = args.export(&db, &mut out).await?;
+            out.flush()?;
+            println!("Wrote {} rows to {}", count, path.display());
+        }
+        Cli::Stats => stats(&current_db().await?).await?,
+        Cli::Import {
+            what,
+            path,
+            guild,
+            map,
+            dry_run,
+        } => {
+            let db = current_db().await?;
+            let options = import::Options {
+                guild,
+                map,
+                dry_run,
+            };
+            let summary = import::import(&db, what, &path, &options).await?;
+            for (line, e) in &summary.failed {
+                println!("Line {}: {}", line, e);
+            }
+            println!(
+                "{} {}, skipped {} already imported, {} couldn't be read",
+                if dry_run { "Would import" } else { "Imported" },
+                summary.added,
+                summary.duplicates,
+                summary.failed.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// the database as it is. opening it doesn't create, migrate or tidy it
+async fn existing_db() -> anyhow::Result<Database> {
+    if !Path::new(DB_FILE).exists() {
+        bail!("There's no database at {}", DB_FILE);
+    }
+    Ok(Database::open(DB_FILE).await?)
+}
+
+/// like [`existing_db`], but it has to be migrated, since it gets used the way the bot uses it
+async fn current_db() -> anyhow::Result<Database> {
+    let db = existing_db().await?;
+    if !db.pending_migrations().await?.is_empty() {
+        bail!("The database is out of date, run `lurk_chan migrate` first");
+    }
+    Ok(db)
+}
+
+fn parse_map(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(from, to)| (from.trim().to_string(), to.trim().to_string()))
+        .ok_or_else(|| "should look like from=to".to_string())
+}
+
+/// fails if anything is wrong, so scripts can tell
+async fn integrity(db: &Database) -> anyhow::Result<()> {
+    backups::check(db).await?;
+    println!("Database is OK");
+    Ok(())
+}
+
+/// check the backup, put the current database aside and the backup in its place
+async fn restore(path: &Path) -> anyhow::Result<()> {
+    backups::stage(path).await?;
+    if let Some(old) = backups::apply_staged().await? {
+        println!("Moved the old database to {}", old.display());
+    }
+    println!(
+        "Restored {}. It will be migrated the next time the bot starts",
+        path.display()
+    );
+    Ok(())
+}
+
+async fn stats(db: &Database) -> anyhow::Result<()> {
+    println!("Reports: {}", db.total_report_count().await?);
+    for (status, count) in db.report_count_by_status().await? {
+        println!("  {:?}: {}", status, count);
+    }
+    println!("Audits: {}", db.total_action_count().await?);
+    for (kind, count) in db.punishment_counts(None).await? {
+        println!("  {}: {}", Punishment::kind_name(&kind), count);
+    }
+    println!("Report messages: {}", db.get_report_message_count().await?);
+    println!("Audit messages: {}", db.get_action_message_count().await?);
+    println!("Waiting in the outbox: {}", db.outbox_count().await?);
+    println!("Servers set up: {}", db.all_guild_settings().await?.len());
+    Ok(())
+}
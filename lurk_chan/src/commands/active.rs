@@ -0,0 +1,63 @@
+use std::fmt::Write;
+
+use anyhow::Context as _;
+use chrono::{DateTime, Utc};
+use poise::{
+    serenity_prelude::{Color, CreateEmbed},
+    CreateReply,
+};
+
+/// what fits in an embed's description, with room for the "and more" line
+const MAX_DESCRIPTION: usize = 4000;
+
+/// See who's banned or timed out right now
+#[poise::command(slash_command)]
+pub async fn active(ctx: crate::ApplicationContext<'_>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let active = ctx.data().db.active_punishments(guild).await?;
+    let mut description = String::new();
+    let mut shown = 0;
+    for p in &active {
+        let a = &p.action;
+        let until = match p
+            .expires_at
+            .as_deref()
+            .and_then(|t| t.parse::<DateTime<Utc>>().ok())
+        {
+            Some(t) => format!("ends <t:{}:R>", t.timestamp()),
+            None => "never ends".to_string(),
+        };
+        let line = format!(
+            "* **{}** ({}): {}, {} (#{})\n",
+            a.target_username,
+            a.target_id,
+            a.punishment
+                .map_or_else(|| "Other".to_string(), |p| p.to_string()),
+            until,
+            p.id
+        );
+        if description.len() + line.len() > MAX_DESCRIPTION {
+            break;
+        }
+        description += &line;
+        shown += 1;
+    }
+    if shown < active.len() {
+        let _ = writeln!(description, "...and {} more", active.len() - shown);
+    }
+    if description.is_empty() {
+        description = "Nobody! :tada:".to_string();
+    }
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title(format!("{} active punishment(s)", active.len()))
+                    .description(description)
+                    .color(Color::DARK_RED),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
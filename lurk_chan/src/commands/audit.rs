@@ -1,9 +1,10 @@
 use std::time::Duration;
 
 use crate::AuditModal;
-use common::{Action, Location};
-use lurk_chan::execute_modal;
-use poise::serenity_prelude::{CreateMessage, User};
+use anyhow::Context as _;
+use common::{Action, Location, Punishment};
+use lurk_chan::{execute_modal, pick_punishment};
+use poise::serenity_prelude::User;
 /// audit that shit!
 #[poise::command(slash_command, subcommands("discord", "sl"))]
 pub async fn audit(_: crate::Context<'_>) -> anyhow::Result<()> {
@@ -65,6 +66,7 @@ async fn do_it(
     let res = execute_modal(ctx, Some(a), Some(Duration::from_secs(120))).await?;
     match res {
         Some(a) => {
+            let punishment = Punishment::parse(&a.action);
             let a = Action {
                 target_id: a.id,
                 target_username: a.name,
@@ -73,32 +75,23 @@ async fn do_it(
                 server: loc,
                 claimant: ctx.author().id.get(),
                 report: None,
+                punishment,
+                created_at: None,
+                guild_id: Some(ctx.guild_id().context("Not in a guild")?.get()),
             };
-            let channel_for_msg = match a.server {
-                Location::SL => ctx.data().config.secret_lab.audit,
-                Location::Discord => ctx.data().config.discord.audit,
-            };
-            let aid = ctx.data().db.add_action(a.clone()).await?;
-            let m = channel_for_msg
-                .send_message(
-                    ctx.serenity_context(),
-                    CreateMessage::default()
-                        .embed(
-                            lurk_chan::create_action_embed(
-                                &a,
-                                &ctx.serenity_context(),
-                                aid,
-                                channel_for_msg,
-                            )
-                            .await?,
-                        )
-                        .components(lurk_chan::create_action_components(aid)),
-                )
-                .await?;
-            ctx.data()
-                .db
-                .add_action_message(m.channel_id.get(), m.id.get(), aid)
-                .await?;
+            let id = ctx.data().db.add_action(a).await?;
+            crate::tasks::flush_outbox(ctx.serenity_context(), ctx.data()).await;
+            if pick_punishment(
+                ctx.serenity_context(),
+                &ctx.data().db,
+                &ctx.interaction.token,
+                id,
+                punishment,
+            )
+            .await?
+            {
+                crate::tasks::flush_outbox(ctx.serenity_context(), ctx.data()).await;
+            }
             Ok(())
         }
         None => Ok(()),
@@ -0,0 +1,123 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use database::BACKUP_DIR;
+use poise::{
+    serenity_prelude::{Color, CreateEmbed},
+    CreateReply,
+};
+use tracing::info;
+
+use crate::backups::{self, STAGED_RESTORE};
+
+/// how many backups `/backup list` shows
+const SHOWN: usize = 25;
+
+/// Back up the database, or restore a backup
+#[poise::command(slash_command, subcommands("list", "now", "restore"))]
+pub async fn backup(_: crate::Context<'_>) -> anyhow::Result<()> {
+    // no
+    Ok(())
+}
+
+/// See the backups, newest first
+#[poise::command(slash_command)]
+async fn list(ctx: crate::ApplicationContext<'_>) -> anyhow::Result<()> {
+    let backups = backups::list()?;
+    let mut description = backups.iter().take(SHOWN).fold(String::new(), |mut o, b| {
+        let _ = writeln!(
+            o,
+            "`{}` {:.1} MiB, <t:{}:R>",
+            b.name,
+            b.size as f64 / (1024.0 * 1024.0),
+            b.modified.timestamp()
+        );
+        o
+    });
+    if backups.len() > SHOWN {
+        let _ = writeln!(description, "...and {} older", backups.len() - SHOWN);
+    }
+    if Path::new(STAGED_RESTORE).exists() {
+        description += "\nA backup is waiting to be restored the next time lurk-chan starts";
+    }
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title(format!("{} backup(s)", backups.len()))
+                    .description(description)
+                    .color(Color::BLURPLE),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Back the database up right now
+#[poise::command(slash_command)]
+async fn now(ctx: crate::ApplicationContext<'_>) -> anyhow::Result<()> {
+    ctx.defer_ephemeral().await?;
+    let path = backups::create(ctx.data()).await?;
+    ctx.send(
+        CreateReply::default()
+            .content(format!("Backed up to `{}`", path.display()))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn autocomplete_backup<'a>(
+    _: crate::Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    backups::list()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|b| b.name)
+        .filter(move |n| n.contains(partial))
+        .take(25)
+}
+
+/// Restore a backup the next time lurk-chan starts
+#[poise::command(slash_command)]
+async fn restore(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "Which one? (from /backup list)"]
+    #[autocomplete = "autocomplete_backup"]
+    name: String,
+) -> anyhow::Result<()> {
+    // only ever things in the backups folder
+    if Path::new(&name).file_name() != Some(name.as_ref()) {
+        ctx.send(
+            CreateReply::default()
+                .content("That's not a backup, pick one from `/backup list`")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    ctx.defer_ephemeral().await?;
+    let path = PathBuf::from(BACKUP_DIR).join(&name);
+    if let Err(e) = backups::stage(&path).await {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("Can't restore `{}`: {:#}", name, e))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    info!("{} staged {} to be restored", ctx.author().name, name);
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "`{}` is checked and ready. Restart lurk-chan to restore it, anything since it was taken will be lost (the current database is kept aside). Delete `{}` to change your mind",
+                name, STAGED_RESTORE
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
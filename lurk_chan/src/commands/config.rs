@@ -0,0 +1,162 @@
+use anyhow::Context as _;
+use poise::{
+    serenity_prelude::{Color, CreateEmbed},
+    ChoiceParameter as _, CreateReply,
+};
+use tracing::info;
+
+use crate::{
+    permissions::{self, Tier},
+    settings::{self, Key, Settings},
+};
+
+/// Change how lurk-chan works in this server
+#[poise::command(slash_command, subcommands("get", "set", "reset"))]
+pub async fn config(_: crate::Context<'_>) -> anyhow::Result<()> {
+    // no
+    Ok(())
+}
+
+/// See the settings
+#[poise::command(slash_command)]
+async fn get(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "Which one? (default: all of them)"] key: Option<Key>,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let lc = ctx.data();
+    let settings = Settings::load(lc, guild).await?;
+    let stored = lc.db.settings(guild).await?;
+    let keys = match key {
+        Some(k) => vec![k],
+        None => Key::ALL.to_vec(),
+    };
+    let mut embed = CreateEmbed::default()
+        .title("Settings")
+        .color(Color::BLURPLE);
+    for key in keys {
+        // channels don't have defaults, they're just not set
+        let default = !key.is_channel() && !stored.iter().any(|(k, _)| k == key.name());
+        embed = embed.field(
+            key.name(),
+            format!(
+                "{}{}\n*{}*",
+                settings.show(key),
+                if default { " (default)" } else { "" },
+                key.describe()
+            ),
+            true,
+        );
+    }
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Change a setting
+#[poise::command(slash_command)]
+async fn set(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "Which one?"] key: Key,
+    #[description = "What to set it to"] value: String,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    let value = match settings::parse(ctx.serenity_context(), guild, key, &value).await {
+        Ok(v) => v,
+        Err(e) => {
+            ctx.send(CreateReply::default().content(e).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    change(ctx, key, Some(value)).await
+}
+
+/// Put a setting back to its default
+#[poise::command(slash_command)]
+async fn reset(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "Which one?"] key: Key,
+) -> anyhow::Result<()> {
+    change(ctx, key, None).await
+}
+
+/// set `key` to `value`, or back to the default for None
+async fn change(
+    ctx: crate::ApplicationContext<'_>,
+    key: Key,
+    value: Option<String>,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let lc = ctx.data();
+    let member = ctx.author_member().await;
+    // the roles for a tier are only up to people at that tier or above, so admins can't make
+    // themselves owners or take roles away from the owners
+    if let Some(tier) = key.tier() {
+        let old = Settings::load(lc, guild).await?;
+        let bot_owner = ctx.framework().options().owners.contains(&ctx.author().id);
+        if !bot_owner && permissions::tier_of(old.roles.as_ref(), member.as_deref()) < tier {
+            ctx.send(
+                CreateReply::default()
+                    .content(format!("Only a {} or above can change that!", tier))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+    let mut channels = lc.db.guild_settings(guild).await?.unwrap_or_default();
+    channels.guild_id = guild;
+    let mut stored = lc.db.settings(guild).await?;
+    stored.retain(|(k, _)| k != key.name());
+    if let Some(c) = key.channel(&mut channels) {
+        *c = value.as_deref().map(str::parse).transpose()?;
+    } else if let Some(v) = &value {
+        stored.push((key.name().to_string(), v.clone()));
+    }
+    let new = Settings::from_stored(&lc.config.get(), channels.clone(), &stored)?;
+    // nobody gets to lock themselves out
+    if key.tier().is_some()
+        && permissions::tier_of(new.roles.as_ref(), member.as_deref()) < Tier::Admin
+    {
+        ctx.send(
+            CreateReply::default()
+                .content(
+                    "That would lock you out of `/config`! Give yourself one of those roles first.",
+                )
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // even if it's not a channel, so the guild is known about
+    lc.db.set_guild_settings(&channels).await?;
+    if !key.is_channel() {
+        match &value {
+            Some(v) => {
+                lc.db
+                    .set_setting(guild, key.name(), v, ctx.author().id.get())
+                    .await?
+            }
+            None => {
+                lc.db.reset_setting(guild, key.name()).await?;
+            }
+        }
+    }
+    info!(
+        "{} ({}) set {} to {:?} in {}",
+        ctx.author().name,
+        ctx.author().id,
+        key.name(),
+        value,
+        guild
+    );
+    ctx.send(
+        CreateReply::default()
+            .content(format!("`{}` is now {}", key.name(), new.show(key)))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
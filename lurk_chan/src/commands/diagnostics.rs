@@ -0,0 +1,50 @@
+use std::fmt::Write;
+
+use anyhow::Context as _;
+use poise::{
+    serenity_prelude::{Color, CreateEmbed},
+    CreateReply,
+};
+
+use crate::preflight;
+
+/// Check that lurk-chan can see and post in everything it needs to
+#[poise::command(slash_command)]
+pub async fn diagnostics(ctx: crate::ApplicationContext<'_>) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?;
+    ctx.defer_ephemeral().await?;
+    let checks = preflight::run(ctx.serenity_context(), ctx.data()).await?;
+    // other servers are none of this one's business
+    let checks: Vec<_> = checks
+        .into_iter()
+        .filter(|c| c.guild.is_none() || c.guild == Some(guild))
+        .collect();
+    let problems = checks.iter().filter(|c| c.problem.is_some()).count();
+    let description = checks.iter().fold(String::new(), |mut o, c| {
+        let _ = match &c.problem {
+            None => writeln!(o, ":white_check_mark: {}", c.what),
+            Some(p) => writeln!(o, ":x: {} {}", c.what, p),
+        };
+        o
+    });
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title(if problems == 0 {
+                        "Everything looks good!".to_string()
+                    } else {
+                        format!("{} problem(s) found", problems)
+                    })
+                    .description(description)
+                    .color(if problems == 0 {
+                        Color::from_rgb(0, 255, 0)
+                    } else {
+                        Color::from_rgb(255, 0, 0)
+                    }),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
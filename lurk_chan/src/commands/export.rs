@@ -0,0 +1,137 @@
+use anyhow::Context as _;
+use chrono::Duration;
+use common::{Location, ReportStatus};
+use database::{ExportFilter, ExportFormat, ExportTable};
+use lurk_chan::db_time;
+use poise::{
+    serenity_prelude::{CreateAttachment, User},
+    CreateReply,
+};
+
+use super::leaderboard::parse_day;
+
+/// discord won't take anything bigger than this from a bot
+const MAX_ATTACHMENT: usize = 8 * 1024 * 1024;
+
+#[derive(poise::ChoiceParameter)]
+enum What {
+    Reports,
+    Audits,
+    #[name = "Audit edits"]
+    Edits,
+}
+
+#[derive(poise::ChoiceParameter)]
+enum Format {
+    #[name = "CSV (spreadsheets)"]
+    Csv,
+    #[name = "JSON lines"]
+    Jsonl,
+}
+
+#[derive(poise::ChoiceParameter)]
+enum Where {
+    SL,
+    Discord,
+}
+
+#[derive(poise::ChoiceParameter)]
+enum Status {
+    Open,
+    Claimed,
+    Closed,
+    Expired,
+}
+
+/// Download reports or audits as a spreadsheet
+#[poise::command(slash_command)]
+#[allow(clippy::too_many_arguments)]
+pub async fn export(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "What to export"] what: What,
+    #[description = "Which format? (default: CSV)"] format: Option<Format>,
+    #[description = "Only from this day on (YYYY-MM-DD)"] from: Option<String>,
+    #[description = "Only up to this day, inclusive (YYYY-MM-DD)"] to: Option<String>,
+    #[description = "Only from SL or discord"] location: Option<Where>,
+    #[description = "Only reports with this status"] status: Option<Status>,
+    #[description = "Only things claimed or done by them"] claimant: Option<User>,
+    #[description = "Only things about this player (steam/discord id)"] target: Option<String>,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let (since, until) = match (from.as_deref().map(parse_day), to.as_deref().map(parse_day)) {
+        (Some(None), _) | (_, Some(None)) => {
+            ctx.send(
+                CreateReply::default()
+                    .content("Dates need to look like 2023-11-25!")
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+        // `to` is inclusive, so go to the end of that day
+        (since, until) => (
+            since.flatten().map(db_time),
+            until.flatten().map(|t| db_time(t + Duration::days(1))),
+        ),
+    };
+    let filter = ExportFilter {
+        // other servers are none of this one's business
+        guild: Some(guild),
+        since,
+        until,
+        location: location.map(|l| match l {
+            Where::SL => Location::SL,
+            Where::Discord => Location::Discord,
+        }),
+        status: status.map(|s| match s {
+            Status::Open => ReportStatus::Open,
+            Status::Claimed => ReportStatus::Claimed,
+            Status::Closed => ReportStatus::Closed,
+            Status::Expired => ReportStatus::Expired,
+        }),
+        claimant: claimant.map(|u| u.id.get()),
+        target: target.map(|t| t.trim().to_string()),
+    };
+    let (table, name) = match what {
+        What::Reports => (ExportTable::Reports, "reports"),
+        What::Audits => (ExportTable::Audits, "audits"),
+        What::Edits => (ExportTable::Edits, "audit_edits"),
+    };
+    let format = match format.unwrap_or(Format::Csv) {
+        Format::Csv => ExportFormat::Csv,
+        Format::Jsonl => ExportFormat::Jsonl,
+    };
+    ctx.defer_ephemeral().await?;
+    let mut out = vec![];
+    let count = ctx
+        .data()
+        .db
+        .export(table, format, &filter, &mut out)
+        .await?;
+    if out.len() > MAX_ATTACHMENT {
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "That's {} rows, too many for discord! Narrow it down, or use `export` in the console",
+                    count
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    let file = format!(
+        "{}_{}.{}",
+        name,
+        chrono::Utc::now().format("%Y-%m-%d"),
+        format.extension()
+    );
+    ctx.send(
+        CreateReply::default()
+            .content(format!("{} rows", count))
+            .attachment(CreateAttachment::bytes(out, file))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use anyhow::Context as _;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use lurk_chan::{LeaderboardMetric, LeaderboardQuery};
+use poise::CreateReply;
+
+use crate::settings::Settings;
+
+#[derive(poise::ChoiceParameter)]
+enum Period {
+    #[name = "Last 7 days"]
+    Week,
+    #[name = "Last 30 days"]
+    Month,
+    #[name = "This month"]
+    ThisMonth,
+    #[name = "All time"]
+    AllTime,
+    #[name = "Custom (use from/to)"]
+    Custom,
+}
+
+#[derive(poise::ChoiceParameter)]
+enum Metric {
+    #[name = "Reports claimed"]
+    Reports,
+    #[name = "Audits made"]
+    Audits,
+}
+
+impl From<Metric> for LeaderboardMetric {
+    fn from(m: Metric) -> Self {
+        match m {
+            Metric::Reports => LeaderboardMetric::Reports,
+            Metric::Audits => LeaderboardMetric::Audits,
+        }
+    }
+}
+
+/// See who has been doing the most work
+#[poise::command(slash_command)]
+pub async fn leaderboard(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "Over what time? (default: all time)"] period: Option<Period>,
+    #[description = "What to count? (default: reports)"] metric: Option<Metric>,
+    #[description = "Start of a custom period (YYYY-MM-DD)"] from: Option<String>,
+    #[description = "End of a custom period, inclusive (YYYY-MM-DD)"] to: Option<String>,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let (since, until) = match period.unwrap_or(Period::AllTime) {
+        Period::Week => (Some(now - Duration::days(7)), None),
+        Period::Month => (Some(now - Duration::days(30)), None),
+        Period::ThisMonth => (
+            NaiveDate::from_ymd_opt(now.year(), now.month(), 1)
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|d| d.and_utc()),
+            None,
+        ),
+        Period::AllTime => (None, None),
+        Period::Custom => {
+            let (Some(since), Some(until)) =
+                (from.as_deref().map(parse_day), to.as_deref().map(parse_day))
+            else {
+                ctx.send(
+                    CreateReply::default()
+                        .content("A custom period needs both `from` and `to`!")
+                        .ephemeral(true),
+                )
+                .await?;
+                return Ok(());
+            };
+            match (since, until) {
+                (Some(since), Some(until)) if since <= until => {
+                    // `to` is inclusive, so go to the end of that day
+                    (Some(since), Some(until + Duration::days(1)))
+                }
+                _ => {
+                    ctx.send(
+                        CreateReply::default()
+                            .content("Dates need to look like 2023-11-25, and `from` has to be before `to`!")
+                            .ephemeral(true),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+    let q = LeaderboardQuery {
+        metric: metric.unwrap_or(Metric::Reports).into(),
+        since,
+        until,
+    };
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let size = Settings::load(ctx.data(), guild).await?.leaderboard_size;
+    let (embed, comp) =
+        lurk_chan::create_leaderboard_page(&q, 0, guild, size, &ctx.data().db).await?;
+    ctx.send(
+        CreateReply::default()
+            .embed(embed)
+            .components(comp)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// start of the day, in UTC
+pub fn parse_day(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+        .ok()?
+        .and_hms_opt(0, 0, 0)
+        .map(|d| d.and_utc())
+}
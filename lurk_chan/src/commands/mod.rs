@@ -1,55 +1,53 @@
 use std::time::Duration;
+
+use crate::permissions::Tier;
+mod active;
 mod audit;
+mod backup;
+mod config;
+mod diagnostics;
+mod export;
+mod leaderboard;
 mod move_thing;
 pub mod past;
 mod ping;
 mod report;
 mod report_to_admin;
+mod search;
+mod setup;
 
 pub fn commands() -> Vec<poise::Command<crate::LurkChan, anyhow::Error>> {
     vec![
-        ping::ping(),
-        report::report(),
-        audit::audit(),
-        past::past(),
-        report_to_admin::report_to_admins(),
-        move_thing::move_command(),
+        (ping::ping(), Tier::Everyone),
+        (report::report(), Tier::TrialMod),
+        (audit::audit(), Tier::Mod),
+        (past::past(), Tier::TrialMod),
+        (active::active(), Tier::TrialMod),
+        (report_to_admin::report_to_admins(), Tier::Everyone),
+        (move_thing::move_command(), Tier::Mod),
+        (search::search(), Tier::TrialMod),
+        (leaderboard::leaderboard(), Tier::TrialMod),
+        (setup::setup(), Tier::Owner),
+        (config::config(), Tier::Admin),
+        (diagnostics::diagnostics(), Tier::Admin),
+        (export::export(), Tier::Admin),
+        (backup::backup(), Tier::Owner),
     ]
     .into_iter()
-    .map(|mut i| {
+    .map(|(mut i, tier)| {
         i.guild_only = true;
         i.subcommand_required = true;
-        //i.default_member_permissions = Permissions::MUTE_MEMBERS;
-        /*i.checks
-        .push(|ctx: crate::Context| Box::pin(async move { check_perms(ctx).await }));*/
+        set_tier(&mut i, tier);
         i.cooldown_config.write().unwrap().user = Some(Duration::from_secs(5));
         i
     })
     .collect()
 }
 
-/*async fn check_perms(ctx: crate::Context<'_>) -> anyhow::Result<bool> {
-    if ctx
-        .author_member()
-        .await
-        .context("nah")?
-        .roles
-        .iter()
-        .any(|role| {
-            role.to_role_cached(ctx).is_some_and(|e| {
-                e.permissions.contains(Permissions::ADMINISTRATOR)
-                    || e.name.contains("Admin")
-                    || e.name.contains("Mod")
-            })
-        }) {
-            Ok(true)
-        } else {
-            ctx.send(
-                CreateReply::default()
-                    .content("You don't have permission to do that!")
-                    .ephemeral(true),
-            )
-            .await?;
-            Ok(false)
-        }
-}*/
+/// store the tier a command needs where [`crate::permissions::check_command`] can find it
+fn set_tier(cmd: &mut poise::Command<crate::LurkChan, anyhow::Error>, tier: Tier) {
+    cmd.custom_data = Box::new(tier);
+    for sub in &mut cmd.subcommands {
+        set_tier(sub, tier);
+    }
+}
@@ -1,8 +1,7 @@
+use crate::{permissions, settings::Settings};
+use anyhow::Context as _;
 use common::Location;
-use poise::{
-    serenity_prelude::{ChannelId, CreateMessage, MessageId, Timestamp},
-    CreateReply,
-};
+use poise::{serenity_prelude::Timestamp, CreateReply};
 
 #[derive(poise::ChoiceParameter)]
 enum Choices {
@@ -35,10 +34,22 @@ async fn audit(
     let location: Location = location.into();
     ctx.defer_ephemeral().await?;
 
-    let current_audit = ctx.data().db.get_action_from_id(id).await?;
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let current_audit = ctx
+        .data()
+        .db
+        .get_action_from_id(id)
+        .await?
+        // other servers' audits don't exist here
+        .filter(|a| a.guild_id == Some(guild));
 
     if let Some(mut a) = current_audit {
-        if ctx.author().id.get() != a.claimant {
+        let settings = Settings::for_guild(ctx.data(), ctx.guild_id()).await?;
+        let tier = permissions::tier_of(
+            settings.roles.as_ref(),
+            ctx.author_member().await.as_deref(),
+        );
+        if ctx.author().id.get() != a.claimant && !permissions::can_override_claimant(tier) {
             ctx.send(
                 CreateReply::default()
                     .content("You can't move someone else's audit!")
@@ -57,73 +68,34 @@ async fn audit(
             return Ok(());
         }
         // first, edit the audit
+        let old = a.clone();
         a.server = location.clone();
-        // get the old message
-        let new_channel = match a.server {
-            Location::SL => ctx.data().config.secret_lab.audit,
-            Location::Discord => ctx.data().config.discord.audit,
-        };
-        let old_message = ctx.data().db.get_action_message(id).await?;
-        if let Some((channel_id, message_id)) = old_message {
-            let channel_id = ChannelId::new(channel_id);
-            let message_id = MessageId::new(message_id);
-            let old_message = channel_id
-                .message(ctx.serenity_context(), message_id)
-                .await?;
-
-            // update db
-
-            ctx.data()
-                .db
-                .edit_action(
-                    id,
-                    a.clone(),
-                    Timestamp::now().to_string(),
-                    ctx.author().id.get(),
-                )
-                .await?;
-
-            // send a new message
-            let new_message = new_channel
-                .send_message(
-                    ctx.serenity_context(),
-                    CreateMessage::default()
-                        .embed(
-                            lurk_chan::create_action_embed(
-                                &a,
-                                &ctx.serenity_context(),
-                                id,
-                                new_channel,
-                            )
-                            .await?,
-                        )
-                        .components(lurk_chan::create_action_components(id)),
-                )
-                .await?;
 
-            // remove old message
-            old_message.delete(ctx.serenity_context()).await?;
-
-            // update DB
-            ctx.data()
-                .db
-                .add_action_message(new_message.channel_id.get(), new_message.id.get(), id)
-                .await?;
-
-            ctx.send(
-                CreateReply::default()
-                    .content(format!("Audit #{} moved to {:?}!", id, location))
-                    .ephemeral(true),
-            )
-            .await?;
+        // update db. the new message and deleting the old one go through the outbox
+        let uid = ctx.author().id.get();
+        let claimant = if permissions::can_override_claimant(tier) {
+            None
         } else {
-            ctx.send(
-                CreateReply::default()
-                    .content(format!("Audit #{} not found!", id))
-                    .ephemeral(true),
-            )
+            Some(uid)
+        };
+        let outcome = ctx
+            .data()
+            .db
+            .move_action(id, &old, a, Timestamp::now().to_string(), uid, claimant)
             .await?;
+        if let Some(msg) = lurk_chan::outcome_message(&outcome, uid) {
+            ctx.send(CreateReply::default().content(msg).ephemeral(true))
+                .await?;
+            return Ok(());
         }
+        crate::tasks::flush_outbox(ctx.serenity_context(), ctx.data()).await;
+
+        ctx.send(
+            CreateReply::default()
+                .content(format!("Audit #{} moved to {:?}!", id, location))
+                .ephemeral(true),
+        )
+        .await?;
     } else {
         ctx.send(
             CreateReply::default()
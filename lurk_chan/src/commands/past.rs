@@ -1,3 +1,4 @@
+use anyhow::Context as _;
 use poise::{
     serenity_prelude::{CreateEmbed, CreateEmbedFooter},
     CreateReply,
@@ -8,7 +9,8 @@ pub async fn past(
     ctx: crate::ApplicationContext<'_>,
     #[description = "who?"] who: String,
 ) -> anyhow::Result<()> {
-    let info = ctx.data().db.collect_user_info(&who).await?;
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let info = ctx.data().db.collect_user_info(&who, guild).await?;
 
     let reported_embed = CreateEmbed::default()
         .title(format!("Reports against {}", who))
@@ -1,3 +1,4 @@
+use anyhow::Context as _;
 use poise::CreateReply;
 /// Get a report by its id
 #[poise::command(slash_command)]
@@ -5,7 +6,14 @@ pub async fn report(
     ctx: crate::Context<'_>,
     #[description = "Report to get"] report_id: u32,
 ) -> anyhow::Result<()> {
-    let report = ctx.data().db.get_report_from_id(report_id).await?;
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let report = ctx
+        .data()
+        .db
+        .get_report_from_id(report_id)
+        .await?
+        // other servers' reports don't exist here
+        .filter(|r| r.guild_id == Some(guild));
     match report {
         Some(r) => {
             let embed = lurk_chan::create_report_embed(&r, report_id, &ctx.data().db).await?;
@@ -1,7 +1,7 @@
 use anyhow::Context;
 use common::Report;
 use poise::{
-    serenity_prelude::{CreateMessage, Timestamp},
+    serenity_prelude::{ChannelId, CreateMessage, Timestamp},
     CreateReply, Modal,
 };
 use std::time::Duration;
@@ -11,6 +11,23 @@ pub async fn report_to_admins(
     ctx: crate::ApplicationContext<'_>,
     message: poise::serenity_prelude::Message,
 ) -> anyhow::Result<()> {
+    let lc = ctx.data();
+    let guild = ctx.guild_id().context("not in a guild")?;
+    let Some(channel_id) = lc
+        .db
+        .guild_settings(guild.get())
+        .await?
+        .and_then(|s| s.discord_reports)
+    else {
+        ctx.send(
+            CreateReply::default()
+                .content("Reports aren't set up here yet! Ask an owner to run `/setup`.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+    let channel_id = ChannelId::new(channel_id);
     let resp: Option<ReportModal> =
         lurk_chan::execute_modal(ctx, None, Some(Duration::from_secs(300))).await?;
 
@@ -45,9 +62,10 @@ pub async fn report_to_admins(
             time: Timestamp::now().to_string(),
             claimant: None,
             location: common::Location::Discord,
+            claimed_at: None,
+            closed_at: None,
+            guild_id: Some(guild.get()),
         };
-        let lc = ctx.data();
-        let channel_id = lc.config.discord.reports;
         let id = lc.db.add_report(report.clone()).await?;
         // send the report message
         let (embed, comp) = lurk_chan::create_things_from_report(report, id, &lc.db).await?;
@@ -0,0 +1,149 @@
+use std::fmt::Write;
+
+use anyhow::Context as _;
+use lurk_chan::do_sl_subs;
+use poise::{
+    serenity_prelude::{Color, CreateEmbed, CreateEmbedFooter},
+    CreateReply,
+};
+
+const PER_PAGE: u32 = 10;
+/// a query longer than this is cut off in the title, which discord caps at 256 characters
+const MAX_TITLE_QUERY: usize = 200;
+
+/// Search through old reports and audits
+#[poise::command(slash_command, subcommands("reports", "audits"))]
+pub async fn search(_: crate::Context<'_>) -> anyhow::Result<()> {
+    // no
+    Ok(())
+}
+
+/// Search report reasons and player names
+#[poise::command(slash_command)]
+async fn reports(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "What to look for"] query: String,
+    #[description = "Page of results"]
+    #[min = 1]
+    #[max = 1000]
+    page: Option<u32>,
+) -> anyhow::Result<()> {
+    if query.trim().is_empty() {
+        return blank_query(ctx).await;
+    }
+    let page = page.unwrap_or(1).max(1);
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let (total, hits) = ctx
+        .data()
+        .db
+        .search_reports(guild, &query, page - 1, PER_PAGE)
+        .await?;
+    let description = hits.into_iter().fold(String::new(), |mut o, hit| {
+        let _ = writeln!(
+            o,
+            "* **#{}** {} ({}) reported by {}: {}",
+            hit.id,
+            do_sl_subs(&hit.item.reported_name),
+            hit.item.reported_id,
+            do_sl_subs(&hit.item.reporter_name),
+            do_sl_subs(&hit.snippet)
+        );
+        o
+    });
+    ctx.send(
+        CreateReply::default()
+            .embed(results_embed(
+                format!("Reports matching '{}'", shorten(&query)),
+                description,
+                page,
+                total,
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Search audit offenses and actions
+#[poise::command(slash_command)]
+async fn audits(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "What to look for"] query: String,
+    #[description = "Page of results"]
+    #[min = 1]
+    #[max = 1000]
+    page: Option<u32>,
+) -> anyhow::Result<()> {
+    if query.trim().is_empty() {
+        return blank_query(ctx).await;
+    }
+    let page = page.unwrap_or(1).max(1);
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let (total, hits) = ctx
+        .data()
+        .db
+        .search_actions(guild, &query, page - 1, PER_PAGE)
+        .await?;
+    let description = hits.into_iter().fold(String::new(), |mut o, hit| {
+        let _ = writeln!(
+            o,
+            "* **#{}** {} ({}) by <@!{}>: {}",
+            hit.id,
+            do_sl_subs(&hit.item.target_username),
+            hit.item.target_id,
+            hit.item.claimant,
+            do_sl_subs(&hit.snippet)
+        );
+        o
+    });
+    ctx.send(
+        CreateReply::default()
+            .embed(results_embed(
+                format!("Audits matching '{}'", shorten(&query)),
+                description,
+                page,
+                total,
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// fts5 can't search for nothing
+async fn blank_query(ctx: crate::ApplicationContext<'_>) -> anyhow::Result<()> {
+    ctx.send(
+        CreateReply::default()
+            .content("Search for something!")
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+fn shorten(query: &str) -> String {
+    if query.chars().count() > MAX_TITLE_QUERY {
+        format!(
+            "{}...",
+            query.chars().take(MAX_TITLE_QUERY).collect::<String>()
+        )
+    } else {
+        query.to_string()
+    }
+}
+
+fn results_embed(title: String, description: String, page: u32, total: u32) -> CreateEmbed {
+    let pages = total.div_ceil(PER_PAGE).max(1);
+    CreateEmbed::default()
+        .title(title)
+        .description(if description.is_empty() {
+            "Nothing found!".to_string()
+        } else {
+            description
+        })
+        .color(Color::BLURPLE)
+        .footer(CreateEmbedFooter::new(format!(
+            "Page {} of {} ({} results)",
+            page, pages, total
+        )))
+}
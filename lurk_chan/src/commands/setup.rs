@@ -0,0 +1,55 @@
+use anyhow::Context as _;
+use database::GuildSettings;
+use poise::{
+    serenity_prelude::{ChannelId, Color, CreateEmbed, GuildChannel},
+    CreateReply,
+};
+
+/// Set where this server's reports, audits and stats go
+#[poise::command(slash_command)]
+pub async fn setup(
+    ctx: crate::ApplicationContext<'_>,
+    #[description = "Where SL audits go"] sl_audit: Option<GuildChannel>,
+    #[description = "Where discord audits go"] discord_audit: Option<GuildChannel>,
+    #[description = "Where reports from the 'Report Message to Staff' menu go"]
+    discord_reports: Option<GuildChannel>,
+    #[description = "Where the stats message lives"] stats: Option<GuildChannel>,
+) -> anyhow::Result<()> {
+    let guild = ctx.guild_id().context("Not in a guild")?.get();
+    let db = &ctx.data().db;
+    let mut settings = db.guild_settings(guild).await?.unwrap_or(GuildSettings {
+        guild_id: guild,
+        ..Default::default()
+    });
+    // only touch what was given
+    let set = |to: &mut Option<u64>, c: Option<GuildChannel>| {
+        if let Some(c) = c {
+            to.replace(c.id.get());
+        }
+    };
+    set(&mut settings.sl_audit, sl_audit);
+    set(&mut settings.discord_audit, discord_audit);
+    set(&mut settings.discord_reports, discord_reports);
+    set(&mut settings.stats, stats);
+    db.set_guild_settings(&settings).await?;
+
+    let show = |c: Option<u64>| match c {
+        Some(c) => format!("<#{}>", ChannelId::new(c)),
+        None => "Not set".to_string(),
+    };
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title("Server Setup")
+                    .field("SL Audits", show(settings.sl_audit), true)
+                    .field("Discord Audits", show(settings.discord_audit), true)
+                    .field("Discord Reports", show(settings.discord_reports), true)
+                    .field("Stats", show(settings.stats), true)
+                    .color(Color::BLURPLE),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
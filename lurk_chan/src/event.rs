@@ -1,24 +1,22 @@
-use crate::{tasks, AuditModal, LurkChan};
+use crate::{permissions, preflight, settings::Settings, tasks, AuditModal, LurkChan};
 use anyhow::{bail, Context as _};
-use common::{Action, Location, Report};
-use lurk_chan::{
-    create_action_components, create_action_embed, execute_modal_on_component_interaction,
-    transmute_json, update_audit_message, update_report_message,
-};
+use common::{Action, Location, Punishment, Report};
+use database::{GuildSettings, Outcome};
+use lurk_chan::{execute_modal_on_component_interaction, transmute_json, update_report_message};
 use poise::serenity_prelude::{
-    ActivityData, ComponentInteraction, CreateInteractionResponse,
+    ActivityData, ComponentInteraction, ComponentInteractionDataKind, CreateInteractionResponse,
     CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateMessage,
     EditInteractionResponse, FullEvent,
 };
 use poise::serenity_prelude::{
-    AuditLogEntry, CacheHttp, Change, Context, MemberAction, Timestamp, UserId,
+    AuditLogEntry, CacheHttp, Change, Context, GuildId, MemberAction, Timestamp, UserId,
 };
 use poise::{serenity_prelude, FrameworkContext};
 use serenity::all::{CreateEmbed, CreateEmbedFooter};
 use serenity::model::guild::audit_log::Action as AuditAction;
 use std::collections::HashMap;
 use std::time::Duration;
-use tracing::{debug, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 
 #[instrument(skip_all)]
 pub async fn handle(
@@ -37,6 +35,9 @@ pub async fn handle(
                 "for new reports! (v{})",
                 env!("CARGO_PKG_VERSION")
             ))));
+            if let Err(e) = adopt_config_channels(ctx, framework.user_data).await {
+                warn!("Failed to set up a guild from the config: {:?}", e);
+            }
             tasks::start_all_background_tasks(
                 ctx.clone(),
                 framework.user_data.shutdown.clone(),
@@ -44,12 +45,19 @@ pub async fn handle(
             )
             .await?;
         }
+        // the guilds are only all there once the cache is
+        FullEvent::CacheReady { .. } => match preflight::run(ctx, framework.user_data).await {
+            Ok(checks) => preflight::log(&checks),
+            Err(e) => error!("Failed to run the preflight checks: {:?}", e),
+        },
         FullEvent::Message { new_message } => {
             on_message(ctx, new_message, framework.user_data).await?;
         }
         FullEvent::InteractionCreate { interaction } => {
             if let Some(r) = interaction.as_message_component() {
-                if let Err(e) = on_button(ctx, r, framework.user_data).await {
+                // like skip_checks_for_owners does for commands
+                let owner = framework.options().owners.contains(&r.user.id);
+                if let Err(e) = on_button(ctx, r, framework.user_data, owner).await {
                     info!("Error handling button: {:?}", e);
                     r.create_response(
                         ctx,
@@ -64,18 +72,54 @@ pub async fn handle(
                 }
             }
         }
-        FullEvent::GuildAuditLogEntryCreate { entry, .. } => {
-            on_guild_audit(ctx, entry, framework.user_data).await?;
+        FullEvent::GuildAuditLogEntryCreate { entry, guild_id } => {
+            on_guild_audit(ctx, entry, *guild_id, framework.user_data).await?;
         }
         _ => {}
     }
     Ok(())
 }
 
+/// before guilds had their own settings everything was in the config.
+/// if nothing is set up yet, set up the guild those channels are in and give it everything we have
+async fn adopt_config_channels(ctx: &Context, lc: &LurkChan) -> anyhow::Result<()> {
+    if !lc.db.all_guild_settings().await?.is_empty() {
+        return Ok(());
+    }
+    let config = lc.config.get();
+    let (sl, discord) = (&config.secret_lab, &config.discord);
+    let Some(any) = [sl.audit, discord.audit, discord.reports, discord.stats]
+        .into_iter()
+        .flatten()
+        .next()
+    else {
+        return Ok(());
+    };
+    let guild = lurk_chan::guild_of(ctx, any)
+        .await?
+        .context("Config channel isn't in a guild")?;
+    lc.db
+        .set_guild_settings(&GuildSettings {
+            guild_id: guild.get(),
+            sl_audit: sl.audit.map(|c| c.get()),
+            discord_audit: discord.audit.map(|c| c.get()),
+            discord_reports: discord.reports.map(|c| c.get()),
+            stats: discord.stats.map(|c| c.get()),
+        })
+        .await?;
+    let (reports, audits) = lc.db.adopt_guildless(guild.get()).await?;
+    info!(
+        "Set up guild {} from the config, with {} old reports and {} old audits",
+        guild, reports, audits
+    );
+    Ok(())
+}
+
 #[instrument(skip(ctx, entry, lc))]
 pub async fn on_guild_audit(
     ctx: &impl CacheHttp,
     entry: &AuditLogEntry,
+    guild_id: GuildId,
     lc: &LurkChan,
 ) -> anyhow::Result<()> {
     match entry.action {
@@ -103,32 +147,19 @@ pub async fn on_guild_audit(
                                 server: Location::Discord,
                                 claimant: entry.user_id.get(),
                                 report: None,
+                                punishment: Some(Punishment::Timeout(Duration::from_secs(
+                                    (new.unix_timestamp() - Timestamp::now().unix_timestamp())
+                                        .max(0) as u64,
+                                ))),
+                                created_at: None,
+                                guild_id: Some(guild_id.get()),
                             }
                         } else {
                             return Ok(());
                         };
 
-                        let r = lc
-                            .db
-                            .add_action(a.clone())
-                            .await
-                            .context("failed to add action")?;
-                        let (emb, comp) = (
-                            create_action_embed(&a, ctx, r, lc.config.discord.audit).await?,
-                            create_action_components(r),
-                        );
-
-                        let m = lc
-                            .config
-                            .discord
-                            .audit
-                            .send_message(ctx, CreateMessage::default().embed(emb).components(comp))
-                            .await?;
-
-                        lc.db
-                            .add_action_message(m.channel_id.get(), m.id.get(), r)
-                            .await
-                            .context("Failed to add action message")?;
+                        lc.db.add_action(a).await.context("failed to add action")?;
+                        tasks::flush_outbox(ctx, lc).await;
                     }
                     _ => {}
                 }
@@ -145,6 +176,7 @@ pub async fn on_button(
     ctx: &Context,
     int: &ComponentInteraction,
     lc: &LurkChan,
+    owner: bool,
 ) -> anyhow::Result<()> {
     // piss shit anmd die
     info!("Who the fuck touched the button?");
@@ -152,32 +184,150 @@ pub async fn on_button(
         .data
         .custom_id
         .split_once('_')
-        .expect("Invalid custom id, this should never fuckign happen");
+        .context("Bad button id")?;
+    if kind == "punishment" {
+        // answered by whoever's waiting on it, see lurk_chan::pick_punishment
+        if lurk_chan::picker_open(int.message.id) {
+            return Ok(());
+        }
+        int.create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::default()
+                    .content("This picker expired, use Edit on the audit to change it")
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
     //
     //let mut m = int.message.clone();
     //
     
     let uid = int.user.id.get();
+    let settings = Settings::for_guild(lc, int.guild_id).await?;
+    let tier = permissions::tier_of(settings.roles.as_ref(), int.member.as_ref());
+    let needed = permissions::button_tier(kind);
+    if tier < needed && !owner {
+        return permissions::deny_button(ctx, int, tier, needed, settings.roles.is_some()).await;
+    }
+    // admins can act on things that aren't theirs
+    let can_override = permissions::can_override_claimant(tier);
+    // who something has to belong to for the database to go through with it
+    let claimant_guard = if can_override { None } else { Some(uid) };
     match kind {
         "claim" => {
-            let id: u32 = oid.parse().expect("Failed to parse id, fuck!");
+            let id: u32 = oid.parse().context("Bad id in the button")?;
             int.defer_ephemeral(ctx).await?;
-            lc.db.claim_report(id, uid).await?;
+            let outcome = lc.db.claim_report(id, uid).await?;
             update_report_message(ctx, id, &lc.db).await?;
+            if lost_race(ctx, int, &outcome, uid).await? {
+                return Ok(());
+            }
         },
+        "unclaim" => {
+            let id: u32 = oid.parse().context("Bad id in the button")?;
+            int.defer_ephemeral(ctx).await?;
+            let outcome = lc
+                .db
+                .unclaim_report(id, Some(uid), claimant_guard, None)
+                .await?;
+            update_report_message(ctx, id, &lc.db).await?;
+            if lost_race(ctx, int, &outcome, uid).await? {
+                return Ok(());
+            }
+        }
+        "reassign" => {
+            let id: u32 = oid.parse().context("Bad id in the button")?;
+            let ComponentInteractionDataKind::UserSelect { values } = &int.data.kind else {
+                bail!("Reassign wasn't a user select");
+            };
+            let to = *values.first().context("Nobody was picked")?;
+            int.defer_ephemeral(ctx).await?;
+            let member = int
+                .guild_id
+                .context("Not in a guild")?
+                .member(ctx, to)
+                .await?;
+            if permissions::tier_of(settings.roles.as_ref(), Some(&member))
+                < permissions::Tier::TrialMod
+            {
+                int.edit_response(
+                    ctx,
+                    EditInteractionResponse::default().content(format!("<@!{}> isn't staff!", to)),
+                )
+                .await?;
+                return Ok(());
+            }
+            let outcome = lc
+                .db
+                .reassign_report(
+                    id,
+                    uid,
+                    claimant_guard,
+                    to.get(),
+                    Some(&format!("Reassigned to <@!{}>", to)),
+                )
+                .await?;
+            update_report_message(ctx, id, &lc.db).await?;
+            if lost_race(ctx, int, &outcome, uid).await? {
+                return Ok(());
+            }
+        }
+        "reopen" => {
+            let id: u32 = oid.parse().context("Bad id in the button")?;
+            int.defer_ephemeral(ctx).await?;
+            let outcome = lc.db.reopen_report(id, uid).await?;
+            update_report_message(ctx, id, &lc.db).await?;
+            if lost_race(ctx, int, &outcome, uid).await? {
+                return Ok(());
+            }
+        }
         "past" => {
             int.defer_ephemeral(ctx).await?;
             past_btn(int, oid, lc, ctx).await?;
             return Ok(())
         }
+        "leaderboard" => {
+            let (q, page) = lurk_chan::LeaderboardQuery::from_custom_id(oid)
+                .context("Invalid leaderboard button")?;
+            let guild = int.guild_id.context("Not in a guild")?.get();
+            let (embed, comp) = lurk_chan::create_leaderboard_page(
+                &q,
+                page,
+                guild,
+                settings.leaderboard_size,
+                &lc.db,
+            )
+            .await?;
+            int.create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::default()
+                        .embed(embed)
+                        .components(comp),
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+        "timeline" => {
+            let id: u32 = oid.parse().context("Bad id in the button")?;
+            int.defer_ephemeral(ctx).await?;
+            let embed = lurk_chan::create_timeline_embed(id, &lc.db).await?;
+            int.edit_response(ctx, EditInteractionResponse::default().embed(embed))
+                .await?;
+            return Ok(());
+        }
         "close" => {
-            let id: u32 = oid.parse().expect("Failed to parse id, fuck!");
+            let id: u32 = oid.parse().context("Bad id in the button")?;
             let report = lc
                 .db
                 .get_report_from_id(id)
                 .await?
                 .context("That report dont exist")?;
-            if report.claimant.is_some_and(|i| i == uid) {
+            if report.claimant.is_some_and(|i| i == uid) || can_override {
                 // fuck
                 let resp = execute_modal_on_component_interaction(
                     ctx,
@@ -196,6 +346,8 @@ pub async fn on_button(
                     return Ok(());
                 }
                 let resp = resp.unwrap();
+                // saved with a guess right away, so nobody can close it while they pick
+                let punishment = Punishment::parse(&resp.action);
 
                 // create an action from resp
                 let a = Action {
@@ -205,37 +357,33 @@ pub async fn on_button(
                     action: resp.action,
                     server: report.location,
                     report: Some(id),
+                    punishment,
+                    created_at: None,
                     claimant: uid,
+                    guild_id: report.guild_id,
                 };
 
-                let channel_for_msg = match a.server {
-                    Location::SL => lc.config.secret_lab.audit,
-                    Location::Discord => lc.config.discord.audit,
-                };
-                lc.db.close_report(id).await?;
-                let aid = lc.db.add_action(a.clone()).await?;
-                let m = channel_for_msg
-                    .send_message(
+                let (outcome, aid) = lc
+                    .db
+                    .close_report_with_action(id, uid, claimant_guard, a)
+                    .await?;
+                if let Some(msg) = lurk_chan::outcome_message(&outcome, uid) {
+                    update_report_message(ctx, id, &lc.db).await?;
+                    int.create_followup(
                         ctx,
-                        CreateMessage::default()
-                            .embed(
-                                lurk_chan::create_action_embed(&a, ctx, aid, channel_for_msg)
-                                    .await?,
-                            )
-                            .components(lurk_chan::create_action_components(aid)),
+                        CreateInteractionResponseFollowup::default()
+                            .content(msg)
+                            .ephemeral(true),
                     )
                     .await?;
-                lc.db
-                    .add_action_message(m.channel_id.get(), m.id.get(), aid)
-                    .await?;
-                update_report_message(ctx, id, &lc.db).await?;
-                int.create_followup(
-                    ctx,
-                    CreateInteractionResponseFollowup::default()
-                        .content(":+1:")
-                        .ephemeral(true),
-                )
-                .await?;
+                    return Ok(());
+                }
+                // the audit message and the report update go through the outbox
+                tasks::flush_outbox(ctx, lc).await;
+                let aid = aid.context("Closed without an audit")?;
+                if lurk_chan::pick_punishment(ctx, &lc.db, &int.token, aid, punishment).await? {
+                    tasks::flush_outbox(ctx, lc).await;
+                }
                 return Ok(());
             } else {
                 // that doesnt fucking belong to you, dipshit
@@ -252,37 +400,25 @@ pub async fn on_button(
             }
         }
         "forceclose" => {
-            let id: u32 = oid.parse().expect("Failed to parse id, fuck!");
+            let id: u32 = oid.parse().context("Bad id in the button")?;
             int.defer_ephemeral(ctx).await?;
-            let report = lc
+            let outcome = lc
                 .db
-                .get_report_from_id(id)
-                .await?
-                .context("That report dont exist")?;
-            if report.claimant.is_some_and(|i| i == uid) {
-                lc.db.close_report(id).await?;
-                update_report_message(ctx, id, &lc.db).await?;
-            } else {
-                int.create_response(
-                    ctx,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::default()
-                            .content("sorry buddy, that doesn't belong to you")
-                            .ephemeral(true),
-                    ),
-                )
+                .close_report(id, uid, claimant_guard, Some("Closed without action"))
                 .await?;
+            update_report_message(ctx, id, &lc.db).await?;
+            if lost_race(ctx, int, &outcome, uid).await? {
                 return Ok(());
             }
         }
         "edit" => {
-            let id: u32 = oid.parse().expect("Failed to parse id, fuck!");
+            let id: u32 = oid.parse().context("Bad id in the button")?;
             let action = lc
                 .db
                 .get_action_from_id(id)
                 .await?
                 .context("That action do no exis")?;
-            if action.claimant != uid {
+            if action.claimant != uid && !can_override {
                 int.create_response(
                     ctx,
                     CreateInteractionResponse::Message(
@@ -306,25 +442,45 @@ pub async fn on_button(
                 return Ok(());
             }
             let resp = resp.unwrap();
+            // keep what it was unless the action text changed
+            let punishment = (resp.action == action.action)
+                .then_some(action.punishment)
+                .flatten()
+                .or_else(|| Punishment::parse(&resp.action));
             let a = Action {
                 target_id: resp.id,
                 target_username: resp.name,
                 offense: resp.reason,
                 action: resp.action,
-                ..action
+                punishment,
+                ..action.clone()
             };
 
-            lc.db
-                .edit_action(id, a, Timestamp::now().to_string(), uid)
+            let outcome = lc
+                .db
+                .edit_action(
+                    id,
+                    &action,
+                    a,
+                    Timestamp::now().to_string(),
+                    uid,
+                    claimant_guard,
+                )
                 .await?;
-            update_audit_message(ctx, id, &lc.db).await?;
-            int.create_followup(
-                ctx,
-                CreateInteractionResponseFollowup::default()
-                    .content(":+1:")
-                    .ephemeral(true),
-            )
-            .await?;
+            if let Some(msg) = lurk_chan::outcome_message(&outcome, uid) {
+                int.create_followup(
+                    ctx,
+                    CreateInteractionResponseFollowup::default()
+                        .content(msg)
+                        .ephemeral(true),
+                )
+                .await?;
+                return Ok(());
+            }
+            tasks::flush_outbox(ctx, lc).await;
+            if lurk_chan::pick_punishment(ctx, &lc.db, &int.token, id, punishment).await? {
+                tasks::flush_outbox(ctx, lc).await;
+            }
             return Ok(());
         }
         e => {
@@ -337,6 +493,21 @@ pub async fn on_button(
     Ok(())
 }
 
+/// if `outcome` didn't go through, tell whoever pressed the button why. true if it didn't
+async fn lost_race(
+    ctx: &Context,
+    int: &ComponentInteraction,
+    outcome: &Outcome,
+    uid: u64,
+) -> anyhow::Result<bool> {
+    let Some(msg) = lurk_chan::outcome_message(outcome, uid) else {
+        return Ok(false);
+    };
+    int.edit_response(ctx, EditInteractionResponse::default().content(msg))
+        .await?;
+    Ok(true)
+}
+
 pub fn report_from_msg(msg: &serenity_prelude::Message) -> anyhow::Result<Option<Report>> {
     if let Some(embed) = msg.embeds.get(0) {
         if embed.title.as_deref() != Some("Player Report") {
@@ -368,10 +539,62 @@ async fn on_message(
     new_message: &serenity_prelude::Message,
     lc: &LurkChan,
 ) -> anyhow::Result<()> {
-    if let Some(report) = report_from_msg(new_message)? {
+    ingest_report(ctx, new_message, lc).await?;
+    Ok(())
+}
+
+/// turn a webhook report message into a real report.
+/// false if it isn't one, or it already was turned into one
+pub async fn ingest_report(
+    ctx: &impl CacheHttp,
+    new_message: &serenity_prelude::Message,
+    lc: &LurkChan,
+) -> anyhow::Result<bool> {
+    if let Some(mut report) = report_from_msg(new_message)? {
+        // messages we went and fetched ourselves don't know what guild they're in
+        report.guild_id = match new_message.guild_id {
+            Some(g) => Some(g.get()),
+            None => lurk_chan::guild_of(ctx, new_message.channel_id)
+                .await?
+                .map(|g| g.get()),
+        };
         // holy shit this is a report!
         // add that shit to the db
-        let id = lc.db.add_report(report.clone()).await?;
+        let (channel, message) = (new_message.channel_id.get(), new_message.id.get());
+        let (id, report) = match lc
+            .db
+            .add_report_from_message(report.clone(), channel, message)
+            .await?
+        {
+            Some(id) => (id, report),
+            None => {
+                // we already have it. if we died before posting it, it's never been posted
+                let id = lc
+                    .db
+                    .report_from_source(channel, message)
+                    .await?
+                    .context("Report is a duplicate but not from this message")?;
+                if lc.db.get_report_message(id).await?.is_some() {
+                    // the original just never got deleted
+                    if let Err(e) = new_message.delete(ctx).await {
+                        debug!("Failed to delete already ingested report: {}", e);
+                    }
+                    return Ok(false);
+                }
+                // it might be getting posted right now, by on_message and catch up both seeing it
+                if !lc.db.claim_report_post(id).await? {
+                    debug!("Report #{} is already being posted", id);
+                    return Ok(false);
+                }
+                info!("Report #{} was never posted, posting it now", id);
+                let report = lc
+                    .db
+                    .get_report_from_id(id)
+                    .await?
+                    .context("That report dont exist")?;
+                (id, report)
+            }
+        };
         // send the report message
         let (embed, comp) = lurk_chan::create_things_from_report(report, id, &lc.db).await?;
         let m = new_message
@@ -382,9 +605,9 @@ async fn on_message(
             .add_report_message(m.channel_id.get(), m.id.get(), id)
             .await?;
         new_message.delete(ctx).await?;
-        return Ok(());
+        return Ok(true);
     }
-    Ok(())
+    Ok(false)
 }
 
 
@@ -394,7 +617,8 @@ async fn past_btn(
     lc: &LurkChan,
     ctx: &Context
 ) -> anyhow::Result<()> {
-    let info = lc.db.collect_user_info(&who).await?;
+    let guild = int.guild_id.context("Not in a guild")?.get();
+    let info = lc.db.collect_user_info(&who, guild).await?;
 
     let reported_embed = CreateEmbed::default()
         .title(format!("Reports against {}", who))
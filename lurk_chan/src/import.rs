@@ -0,0 +1,492 @@
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context as _};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use clap::ValueEnum;
+use common::{Action, Location, Punishment, Report, ReportStatus};
+use database::{Database, UNKNOWN_CLAIMANT};
+use lurk_chan::{db_time, format_duration};
+use serde_json::Value;
+
+/// what's in the file
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Kind {
+    /// CSV, JSON or JSON lines, with a row per report
+    Reports,
+    /// CSV, JSON or JSON lines, with a row per audit
+    Audits,
+    /// SL's UserIdBans.txt or IpBans.txt
+    Bans,
+}
+
+pub struct Options {
+    /// where it goes if a row doesn't say
+    pub guild: Option<u64>,
+    /// columns to rename before reading rows, as (from, to)
+    pub map: Vec<(String, String)>,
+    /// only say what would happen
+    pub dry_run: bool,
+}
+
+/// how an import went
+#[derive(Default)]
+pub struct Summary {
+    pub added: usize,
+    /// imported before (or more than once in the file), so skipped
+    pub duplicates: usize,
+    /// rows that couldn't be read, by line (or row) number
+    pub failed: Vec<(usize, String)>,
+}
+
+/// a row from a CSV or JSON file, by column
+type Row = BTreeMap<String, String>;
+/// a line number, and what was on it with its dedupe key (or why it couldn't be read)
+type Parsed = (usize, Result<(String, Entry), String>);
+
+enum Entry {
+    Report(Report),
+    Action(Action),
+}
+
+/// import everything in `path` that wasn't imported before
+pub async fn import(
+    db: &Database,
+    kind: Kind,
+    path: &Path,
+    options: &Options,
+) -> anyhow::Result<Summary> {
+    let source = path
+        .file_name()
+        .context("That's not a file")?
+        .to_string_lossy()
+        .to_string();
+    let guild = match options.guild {
+        Some(g) => Some(g),
+        None => {
+            let guilds = db.all_guild_settings().await?;
+            match guilds.as_slice() {
+                [g] => Some(g.guild_id),
+                // they'll go to the first server set up, like everything from before servers were tracked
+                [] => None,
+                _ => bail!("lurk-chan is in more than one server, say which one with --guild"),
+            }
+        }
+    };
+    let entries: Vec<Parsed> = match kind {
+        Kind::Bans => std::fs::read_to_string(path)
+            .with_context(|| format!("Can't read {}", path.display()))?
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(n, l)| {
+                let ban = parse_ban(l, guild).map(|(k, a)| (k, Entry::Action(a)));
+                (n + 1, ban)
+            })
+            .collect(),
+        Kind::Reports | Kind::Audits => read_rows(path)?
+            .into_iter()
+            .map(|(n, mut row)| {
+                for (from, to) in &options.map {
+                    if let Some(v) = row.remove(from) {
+                        row.insert(to.clone(), v);
+                    }
+                }
+                let entry = match kind {
+                    Kind::Reports => {
+                        to_report(row, guild).map(|r| (report_key(&r), Entry::Report(r)))
+                    }
+                    _ => to_action(row, guild).map(|a| (audit_key(&a), Entry::Action(a))),
+                };
+                (n, entry)
+            })
+            .collect(),
+    };
+
+    let mut summary = Summary::default();
+    let mut seen = HashSet::new();
+    for (n, entry) in entries {
+        let (key, entry) = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                summary.failed.push((n, e));
+                continue;
+            }
+        };
+        if !seen.insert(key.clone()) || db.is_imported(&key).await? {
+            summary.duplicates += 1;
+            continue;
+        }
+        if options.dry_run {
+            summary.added += 1;
+            continue;
+        }
+        let added = match entry {
+            Entry::Report(r) => db.import_report(r, &source, &key).await?,
+            Entry::Action(a) => db.import_action(a, &source, &key).await?,
+        };
+        match added {
+            Some(_) => summary.added += 1,
+            None => summary.duplicates += 1,
+        }
+    }
+    Ok(summary)
+}
+
+/// every row in a CSV, JSON (an array of objects) or JSON lines file, with its line number
+fn read_rows(path: &Path) -> anyhow::Result<Vec<(usize, Row)>> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let read =
+        || std::fs::read_to_string(path).with_context(|| format!("Can't read {}", path.display()));
+    let mut rows = vec![];
+    match ext.as_str() {
+        "csv" => {
+            let mut reader = csv::Reader::from_path(path)
+                .with_context(|| format!("Can't read {}", path.display()))?;
+            let headers = reader.headers()?.clone();
+            for record in reader.records() {
+                let record = record?;
+                let line = record.position().map_or(0, |p| p.line() as usize);
+                let row = headers
+                    .iter()
+                    .zip(record.iter())
+                    .filter(|(_, v)| !v.trim().is_empty())
+                    .map(|(h, v)| (h.trim().to_string(), unescape_csv(v.trim()).to_string()))
+                    .collect();
+                rows.push((line, row));
+            }
+        }
+        "json" => {
+            let Value::Array(items) = serde_json::from_str(&read()?)? else {
+                bail!("A JSON file has to be a list of objects");
+            };
+            for (n, item) in items.into_iter().enumerate() {
+                rows.push((n + 1, json_row(item)?));
+            }
+        }
+        "jsonl" => {
+            for (n, line) in read()?.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let item = serde_json::from_str(line).with_context(|| format!("Line {}", n + 1))?;
+                rows.push((n + 1, json_row(item)?));
+            }
+        }
+        _ => bail!(
+            "Don't know how to read a .{} file, use .csv, .json or .jsonl",
+            ext
+        ),
+    }
+    Ok(rows)
+}
+
+/// `export` puts a `'` in front of anything a spreadsheet would run as a formula, so take it off
+fn unescape_csv(v: &str) -> &str {
+    match v.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(['=', '+', '-', '@', '\t', '\r']) => rest,
+        _ => v,
+    }
+}
+
+fn json_row(item: Value) -> anyhow::Result<Row> {
+    let Value::Object(o) = item else {
+        bail!("Every row has to be an object");
+    };
+    Ok(o.into_iter()
+        .filter_map(|(k, v)| match v {
+            Value::Null => None,
+            Value::String(s) => Some((k, s)),
+            v => Some((k, v.to_string())),
+        })
+        .collect())
+}
+
+/// the typed columns, read the way the database (and `export`) writes them
+fn typed(row: &mut Row, column: &str) -> Result<Option<Value>, String> {
+    let Some(v) = row.remove(column) else {
+        return Ok(None);
+    };
+    let bad = |what: &str| format!("{} {:?} isn't {}", column, v, what);
+    Ok(Some(
+        match column {
+            "report_status" => serde_json::to_value(
+                ReportStatus::from_db(&v.to_lowercase())
+                    .ok_or_else(|| bad("open, claimed, closed or expired"))?,
+            ),
+            "location" | "server" => serde_json::to_value(
+                v.to_lowercase()
+                    .parse::<Location>()
+                    .map_err(|_| bad("sl or discord"))?,
+            ),
+            _ => serde_json::to_value(v.parse::<u64>().map_err(|_| bad("a number"))?),
+        }
+        .expect("plain values"),
+    ))
+}
+
+/// a time in one of the formats spreadsheets tend to have, the way the database has them
+fn normalize_time(s: &str) -> Option<String> {
+    let s = s.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(db_time(t.with_timezone(&Utc)));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(db_time(t.and_utc()));
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()?
+        .and_hms_opt(0, 0, 0)
+        .map(|t| db_time(t.and_utc()))
+}
+
+fn time(what: &str, t: &str) -> Result<String, String> {
+    normalize_time(t).ok_or_else(|| format!("{} {:?} isn't a time", what, t))
+}
+
+/// what makes a report the same report, whichever server it's imported into
+fn report_key(r: &Report) -> String {
+    format!(
+        "report:{}",
+        serde_json::json!([r.reporter_id, r.reported_id, r.time, r.report_reason])
+    )
+}
+
+/// what makes an audit the same audit, whichever server it's imported into
+fn audit_key(a: &Action) -> String {
+    format!(
+        "audit:{}",
+        serde_json::json!([a.target_id, a.action, a.offense, a.created_at])
+    )
+}
+
+/// columns are the ones `common::Report` has (or its webhook names). anything else is ignored.
+/// reports without a status are history, so they're closed
+fn to_report(mut row: Row, guild: Option<u64>) -> Result<Report, String> {
+    let mut o: serde_json::Map<String, Value> = serde_json::Map::new();
+    for column in ["report_status", "location", "claimant", "guild_id"] {
+        if let Some(v) = typed(&mut row, column)? {
+            o.insert(column.to_string(), v);
+        }
+    }
+    o.entry("report_status")
+        .or_insert_with(|| serde_json::to_value(ReportStatus::Closed).expect("plain value"));
+    o.extend(row.into_iter().map(|(k, v)| (k, Value::String(v))));
+    let mut r: Report = serde_json::from_value(Value::Object(o)).map_err(|e| e.to_string())?;
+    r.time = time("time", &r.time)?;
+    r.claimed_at = r.claimed_at.map(|t| time("claimed_at", &t)).transpose()?;
+    r.closed_at = r.closed_at.map(|t| time("closed_at", &t)).transpose()?;
+    r.guild_id = r.guild_id.or(guild);
+    Ok(r)
+}
+
+/// columns are the ones `common::Action` has. audits without a claimant get [`UNKNOWN_CLAIMANT`].
+/// report numbers from somewhere else don't mean anything here, so they're dropped.
+/// the punishment columns from `export` are used if they're there, otherwise it's guessed from `action`
+fn to_action(mut row: Row, guild: Option<u64>) -> Result<Action, String> {
+    row.remove("report");
+    let punishment = row.remove("punishment").map(|kind| {
+        let secs = row.remove("punishment_secs").and_then(|s| s.parse().ok());
+        Punishment::from_db(&kind, secs)
+    });
+    let mut o: serde_json::Map<String, Value> = serde_json::Map::new();
+    for column in ["server", "claimant", "guild_id"] {
+        if let Some(v) = typed(&mut row, column)? {
+            o.insert(column.to_string(), v);
+        }
+    }
+    o.entry("server")
+        .or_insert_with(|| serde_json::to_value(Location::SL).expect("plain value"));
+    o.entry("claimant")
+        .or_insert_with(|| Value::from(UNKNOWN_CLAIMANT));
+    o.extend(row.into_iter().map(|(k, v)| (k, Value::String(v))));
+    let mut a: Action = serde_json::from_value(Value::Object(o)).map_err(|e| e.to_string())?;
+    a.report = None;
+    a.punishment = punishment.unwrap_or_else(|| Punishment::parse(&a.action));
+    a.created_at = a.created_at.map(|t| time("created_at", &t)).transpose()?;
+    a.guild_id = a.guild_id.or(guild);
+    Ok(a)
+}
+
+/// .NET ticks (100ns since 0001-01-01), which SL's ban files use for times
+fn from_ticks(ticks: i64) -> Option<DateTime<Utc>> {
+    const UNIX_EPOCH: i64 = 621_355_968_000_000_000;
+    DateTime::from_timestamp((ticks - UNIX_EPOCH).div_euclid(10_000_000), 0)
+}
+
+/// a line from UserIdBans.txt or IpBans.txt, which look like
+/// `name;id or ip;expires;reason;issuer;issued`, with both times in ticks
+fn parse_ban(line: &str, guild: Option<u64>) -> Result<(String, Action), String> {
+    let fields: Vec<&str> = line.trim().split(';').collect();
+    let [name, id, expires, reason, issuer, issued] = fields[..] else {
+        return Err(format!(
+            "expected 6 fields split by ';', found {}",
+            fields.len()
+        ));
+    };
+    let ticks = |t: &str| {
+        t.trim()
+            .parse::<i64>()
+            .ok()
+            .and_then(from_ticks)
+            .ok_or_else(|| format!("{:?} isn't a time", t))
+    };
+    let (expires, issued) = (ticks(expires)?, ticks(issued)?);
+    let kind = if id.parse::<IpAddr>().is_ok() {
+        "IP banned"
+    } else {
+        "Banned"
+    };
+    let (length, punishment) = if expires.year() >= 9999 {
+        ("permanently".to_string(), Punishment::PermanentBan)
+    } else {
+        let secs = (expires - issued).num_seconds().max(0);
+        (
+            format!("for {}", format_duration(secs as f64)),
+            Punishment::Ban(Duration::from_secs(secs as u64)),
+        )
+    };
+    let action = Action {
+        target_id: id.to_string(),
+        target_username: name.to_string(),
+        offense: reason.to_string(),
+        action: format!("{} {} (in game, by {})", kind, length, issuer),
+        server: Location::SL,
+        claimant: UNKNOWN_CLAIMANT,
+        report: None,
+        punishment: Some(punishment),
+        created_at: Some(db_time(issued)),
+        guild_id: guild,
+    };
+    // the same ban in a newer copy of the file is the same line
+    Ok((format!("sl-ban:{}:{}", id, issued.timestamp()), action))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{import, parse_ban, report_key, to_report, Kind, Options, Row};
+    use common::{Location, Punishment, Report, ReportStatus};
+    use database::{Database, ExportFilter, ExportFormat, ExportTable};
+
+    #[test]
+    fn reads_sl_bans() {
+        let (key, ban) = parse_ban(
+            "Bad Guy;76561198000000000@steam;638400000000000000;Cheating;Admin (76561198000000001@steam);638399136000000000",
+            Some(9),
+        )
+        .unwrap();
+        assert_eq!(key, "sl-ban:76561198000000000@steam:1704316800");
+        assert_eq!(ban.target_username, "Bad Guy");
+        assert_eq!(ban.offense, "Cheating");
+        assert_eq!(
+            ban.action,
+            "Banned for 1d 0h (in game, by Admin (76561198000000001@steam))"
+        );
+        assert_eq!(ban.created_at.as_deref(), Some("2024-01-03T21:20:00Z"));
+        assert_eq!(
+            ban.punishment,
+            Some(Punishment::Ban(Duration::from_secs(24 * 60 * 60)))
+        );
+        assert!(parse_ban("not;enough", None).is_err());
+    }
+
+    #[test]
+    fn reads_spreadsheet_rows() {
+        let row: Row = [
+            ("Reporter UserID", "1@steam"),
+            ("Reporter Nickname", "a"),
+            ("reported_id", "2@steam"),
+            ("reported_name", "b"),
+            ("report_reason", "rude"),
+            ("server", "Server 1"),
+            ("time", "2023-11-25 10:30"),
+            ("claimant", "55"),
+            ("location", "SL"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let r = to_report(row.clone(), Some(9)).unwrap();
+        assert_eq!(r.reporter_id, "1@steam");
+        assert_eq!(r.time, "2023-11-25T10:30:00Z");
+        assert_eq!(r.report_status, ReportStatus::Closed);
+        assert_eq!((r.claimant, r.guild_id), (Some(55), Some(9)));
+        // the same row is the same report, whichever server it's going to
+        let elsewhere = to_report(row.clone(), Some(10)).unwrap();
+        assert_eq!(report_key(&r), report_key(&elsewhere));
+        let mut bad = row;
+        bad.insert("claimant".to_string(), "someone".to_string());
+        assert!(to_report(bad, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn reimports_its_own_export() {
+        let dir = std::env::temp_dir().join(format!("lurk_chan_import_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let open = |name: &str| {
+            let path = dir.join(name);
+            let _ = std::fs::remove_file(&path);
+            std::fs::File::create(&path).unwrap();
+            async move {
+                let db = Database::open(&path).await.unwrap();
+                db.migrate().await.unwrap();
+                db
+            }
+        };
+        let from = open("from.db").await;
+        from.add_report(Report {
+            reporter_id: "1@steam".to_string(),
+            reporter_name: "-Bob".to_string(),
+            reported_id: "2@steam".to_string(),
+            reported_name: "=cmd()".to_string(),
+            report_reason: "rude, very".to_string(),
+            report_status: ReportStatus::Closed,
+            server: "Server 1".to_string(),
+            time: "2023-11-25T10:30:00Z".to_string(),
+            claimant: Some(55),
+            location: Location::SL,
+            claimed_at: None,
+            closed_at: None,
+            guild_id: Some(9),
+        })
+        .await
+        .unwrap();
+        let mut csv = vec![];
+        from.export(
+            ExportTable::Reports,
+            ExportFormat::Csv,
+            &ExportFilter::default(),
+            &mut csv,
+        )
+        .await
+        .unwrap();
+        let file = dir.join("reports.csv");
+        std::fs::write(&file, csv).unwrap();
+
+        let to = open("to.db").await;
+        let options = Options {
+            guild: Some(9),
+            map: vec![],
+            dry_run: false,
+        };
+        let summary = import(&to, Kind::Reports, &file, &options).await.unwrap();
+        assert_eq!((summary.added, summary.failed.len()), (1, 0));
+        let r = to.get_report_from_id(1).await.unwrap().unwrap();
+        assert_eq!(r.reporter_name, "-Bob");
+        assert_eq!(r.reported_name, "=cmd()");
+        assert_eq!(r.report_reason, "rude, very");
+        // and it's the same report the second time
+        let again = import(&to, Kind::Reports, &file, &options).await.unwrap();
+        assert_eq!((again.added, again.duplicates), (0, 1));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,19 @@
 use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::Duration;
 
-use common::{Action, Location, Report, ReportStatus};
-use database::Database;
+use chrono::{DateTime, SecondsFormat, Utc};
+use common::{Action, Location, Punishment, Report, ReportStatus};
+use database::{Database, Outcome};
+use ordinal::Ordinal;
 use poise::serenity_prelude::{
-    ButtonStyle, CacheHttp, ChannelId, Color, CreateActionRow, CreateButton, CreateEmbed,
-    CreateEmbedAuthor, CreateEmbedFooter, EditMessage, MessageId, Timestamp, UserId,
+    futures::StreamExt as _, Builder as _, ButtonStyle, CacheHttp, ChannelId, Color,
+    ComponentInteractionCollector, ComponentInteractionDataKind, Context, CreateActionRow,
+    CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateSelectMenu,
+    CreateSelectMenuKind, CreateSelectMenuOption, EditMessage, GuildId, Message, MessageId,
+    Timestamp, UserId,
 };
 use serde::{de::DeserializeOwned, Serialize};
 /// stupid idiot function to convert serializable to serializable.
@@ -30,6 +39,185 @@ pub fn do_sl_subs(input: &str) -> Cow<str> {
     }
 }
 
+/// how many people are on a page of the leaderboard, unless a guild changed it
+pub const LEADERBOARD_PAGE_SIZE: u32 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMetric {
+    /// reports claimed
+    Reports,
+    /// audits made
+    Audits,
+}
+
+/// what a leaderboard counts, and over what time
+#[derive(Debug, Clone)]
+pub struct LeaderboardQuery {
+    pub metric: LeaderboardMetric,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl LeaderboardQuery {
+    /// custom id of a button that shows `page` of this leaderboard.
+    /// looks like `leaderboard_r:1700000000:-:2`
+    pub fn custom_id(&self, page: u32) -> String {
+        let t = |t: Option<DateTime<Utc>>| match t {
+            Some(t) => t.timestamp().to_string(),
+            None => "-".to_string(),
+        };
+        format!(
+            "leaderboard_{}:{}:{}:{}",
+            match self.metric {
+                LeaderboardMetric::Reports => "r",
+                LeaderboardMetric::Audits => "a",
+            },
+            t(self.since),
+            t(self.until),
+            page
+        )
+    }
+    /// the inverse of [`LeaderboardQuery::custom_id`], without the `leaderboard_` bit
+    pub fn from_custom_id(id: &str) -> Option<(Self, u32)> {
+        let mut parts = id.split(':');
+        let metric = match parts.next()? {
+            "r" => LeaderboardMetric::Reports,
+            "a" => LeaderboardMetric::Audits,
+            _ => return None,
+        };
+        let mut t = || -> Option<Option<DateTime<Utc>>> {
+            match parts.next()? {
+                "-" => Some(None),
+                t => Some(Some(DateTime::from_timestamp(t.parse().ok()?, 0)?)),
+            }
+        };
+        let (since, until) = (t()?, t()?);
+        let page = parts.next()?.parse().ok()?;
+        Some((
+            Self {
+                metric,
+                since,
+                until,
+            },
+            page,
+        ))
+    }
+    fn describe(&self) -> String {
+        match (self.since, self.until) {
+            (None, None) => "All time".to_string(),
+            (Some(s), None) => format!("Since <t:{}:d>", s.timestamp()),
+            (None, Some(u)) => format!("Before <t:{}:d>", u.timestamp()),
+            (Some(s), Some(u)) => format!("<t:{}:d> to <t:{}:d>", s.timestamp(), u.timestamp()),
+        }
+    }
+}
+
+/// the guild a channel is in, None for DMs and such
+pub async fn guild_of(ctx: impl CacheHttp, channel: ChannelId) -> anyhow::Result<Option<GuildId>> {
+    Ok(channel.to_channel(ctx).await?.guild().map(|c| c.guild_id))
+}
+
+/// format a timestamp the way the database stores them
+pub fn db_time(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// one page of a leaderboard, with buttons to flip through the rest of it
+pub async fn create_leaderboard_page(
+    q: &LeaderboardQuery,
+    page: u32,
+    guild: u64,
+    page_size: u32,
+    db: &Database,
+) -> anyhow::Result<(CreateEmbed, Vec<CreateActionRow>)> {
+    use std::fmt::Write;
+    let (since, until) = (q.since.map(db_time), q.until.map(db_time));
+    // the page comes from a button's id, so it could be anything
+    let offset = page.saturating_mul(page_size);
+    let (total, entries) = match q.metric {
+        LeaderboardMetric::Reports => {
+            db.leaderboard_reports_between(
+                guild,
+                since.as_deref(),
+                until.as_deref(),
+                page_size,
+                offset,
+            )
+            .await?
+        }
+        LeaderboardMetric::Audits => {
+            db.leaderboard_audit_between(
+                guild,
+                since.as_deref(),
+                until.as_deref(),
+                page_size,
+                offset,
+            )
+            .await?
+        }
+    };
+    let pages = total.div_ceil(page_size).max(1);
+    let board = entries
+        .into_iter()
+        .enumerate()
+        .fold(String::new(), |mut o, (place, (who, count))| {
+            let _ = writeln!(
+                o,
+                "* {}. <@!{}> - {}",
+                Ordinal(offset as usize + place + 1),
+                who,
+                count
+            );
+            o
+        });
+    let embed = CreateEmbed::default()
+        .title(format!(
+            "{} Leaderboard",
+            match q.metric {
+                LeaderboardMetric::Reports => "Reports",
+                LeaderboardMetric::Audits => "Audits",
+            }
+        ))
+        .description(if board.is_empty() {
+            "Nobody yet!".to_string()
+        } else {
+            board
+        })
+        .field("Period", q.describe(), false)
+        .footer(CreateEmbedFooter::new(format!(
+            "Page {} of {}",
+            page.saturating_add(1),
+            pages
+        )))
+        .color(Color::BLURPLE);
+    let buttons = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(q.custom_id(page.saturating_sub(1)))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(q.custom_id(page.saturating_add(1)))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page.saturating_add(1) >= pages),
+    ])];
+    Ok((embed, buttons))
+}
+
+/// turn a number of seconds into something a human can read, like `1d 4h` or `12m 5s`
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    let (d, h, m, s) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if d > 0 {
+        format!("{}d {}h", d, h)
+    } else if h > 0 {
+        format!("{}h {}m", h, m)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
 mod modal_bullshit;
 pub use modal_bullshit::*;
 pub async fn create_things_from_report(
@@ -48,7 +236,7 @@ pub async fn create_report_embed(
     rid: u32,
     db: &Database,
 ) -> anyhow::Result<CreateEmbed> {
-    let report_count = db.get_report_count(&r.reported_id).await?;
+    let report_count = db.get_report_count(&r.reported_id, r.guild_id).await?;
     let rs = {
         match r.report_status.clone() {
             ReportStatus::Open => "Open".to_string(),
@@ -73,7 +261,7 @@ pub async fn create_report_embed(
             }
         }
     };
-    Ok(CreateEmbed::default()
+    let embed = CreateEmbed::default()
             .title(format!("Report #{}", rid))
             .description("A new report just came in!")
             .field("Reporter ID", do_sl_subs(&r.reporter_id).into_owned(), true)
@@ -99,13 +287,12 @@ pub async fn create_report_embed(
             .footer(CreateEmbedFooter::new(format!(
                 "`/past who:{}` (has been reported {} times)",
                 r.reported_id, report_count
-            )))
-            .timestamp(
-                r.time
-                    .parse::<Timestamp>()
-                    .expect("SL gives a good time"),
-            )
-        )
+            )));
+    // reports with a time that doesn't parse are quarantined, but still get shown
+    Ok(match r.time.parse::<Timestamp>() {
+        Ok(t) => embed.timestamp(t),
+        Err(_) => embed,
+    })
 }
 
 pub async fn create_report_action_row(
@@ -113,31 +300,48 @@ pub async fn create_report_action_row(
     id: u32,
     _db: &Database,
 ) -> anyhow::Result<Vec<CreateActionRow>> {
-    let i: Option<CreateActionRow> = match r.report_status {
-        ReportStatus::Open => Some(CreateActionRow::Buttons(vec![CreateButton::new(format!(
+    let mut base: Vec<CreateActionRow> = match r.report_status {
+        ReportStatus::Open => vec![CreateActionRow::Buttons(vec![CreateButton::new(format!(
             "claim_{}",
             id
         ))
         .label("Claim")
-        .style(ButtonStyle::Primary)])),
-        ReportStatus::Expired => Some(CreateActionRow::Buttons(vec![CreateButton::new(format!(
+        .style(ButtonStyle::Primary)])],
+        ReportStatus::Expired => vec![CreateActionRow::Buttons(vec![CreateButton::new(format!(
             "claim_{}",
             id
         ))
         .label("Reopen and Claim")
-        .style(ButtonStyle::Secondary)])),
-        ReportStatus::Claimed => Some(CreateActionRow::Buttons(vec![
-            CreateButton::new(format!("close_{}", id))
-                .label("Close")
-                .style(ButtonStyle::Primary),
-            CreateButton::new(format!("forceclose_{}", id))
-                .label("Close without action")
-                .style(ButtonStyle::Danger),
-        ])),
-
-        ReportStatus::Closed => None,
+        .style(ButtonStyle::Secondary)])],
+        ReportStatus::Claimed => vec![
+            CreateActionRow::Buttons(vec![
+                CreateButton::new(format!("close_{}", id))
+                    .label("Close")
+                    .style(ButtonStyle::Primary),
+                CreateButton::new(format!("forceclose_{}", id))
+                    .label("Close without action")
+                    .style(ButtonStyle::Danger),
+                CreateButton::new(format!("unclaim_{}", id))
+                    .label("Unclaim")
+                    .style(ButtonStyle::Secondary),
+            ]),
+            CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(
+                    format!("reassign_{}", id),
+                    CreateSelectMenuKind::User {
+                        default_users: None,
+                    },
+                )
+                .placeholder("Reassign to..."),
+            ),
+        ],
+        ReportStatus::Closed => vec![CreateActionRow::Buttons(vec![CreateButton::new(format!(
+            "reopen_{}",
+            id
+        ))
+        .label("Reopen")
+        .style(ButtonStyle::Secondary)])],
     };
-    let mut base = if let Some(a) = i { vec![a] } else { vec![] };
     base.push(CreateActionRow::Buttons(vec![
         CreateButton::new(format!(
             "past_{}",
@@ -151,10 +355,80 @@ pub async fn create_report_action_row(
         ))
             .label("Past (Reporter)")
             .style(ButtonStyle::Secondary),
+        CreateButton::new(format!(
+            "timeline_{}",
+            id
+        ))
+            .label("Timeline")
+            .style(ButtonStyle::Secondary),
     ]));
     Ok(base)
 }
 
+fn status_name(s: &ReportStatus) -> &'static str {
+    match s {
+        ReportStatus::Open => "Open",
+        ReportStatus::Claimed => "Claimed",
+        ReportStatus::Closed => "Closed",
+        ReportStatus::Expired => "Expired",
+    }
+}
+
+/// what to tell `uid` when something they tried didn't go through. None if it did
+pub fn outcome_message(outcome: &Outcome, uid: u64) -> Option<String> {
+    Some(match outcome {
+        Outcome::Done => return None,
+        Outcome::NotFound => "That doesn't exist anymore!".to_string(),
+        Outcome::BelongsTo(c) if *c == uid => "You've already got that one!".to_string(),
+        Outcome::BelongsTo(c) => format!("Too slow! That belongs to <@!{}>", c),
+        Outcome::WrongStatus(s) => format!(
+            "Too slow! That report is {} now",
+            status_name(s).to_lowercase()
+        ),
+        Outcome::Changed => "Someone else edited that while you were, try again!".to_string(),
+    })
+}
+
+/// the history of a report, for the timeline button
+pub async fn create_timeline_embed(rid: u32, db: &Database) -> anyhow::Result<CreateEmbed> {
+    use std::fmt::Write;
+    let events = db.report_events(rid).await?;
+    let description = events.into_iter().fold(String::new(), |mut o, e| {
+        let when = match e.time.parse::<Timestamp>() {
+            Ok(t) => format!("<t:{}:f>", t.unix_timestamp()),
+            Err(_) => e.time.clone(),
+        };
+        let who = match e.actor {
+            Some(a) => format!("<@!{}>", a),
+            None => "Lurk-chan".to_string(),
+        };
+        let _ = match e.old_status {
+            Some(old) => write!(
+                o,
+                "* {} {}: {} → {}",
+                when,
+                who,
+                status_name(&old),
+                status_name(&e.new_status)
+            ),
+            None => write!(o, "* {} Report came in ({})", when, status_name(&e.new_status)),
+        };
+        if let Some(note) = e.note {
+            let _ = write!(o, " ({})", note);
+        }
+        o.push('\n');
+        o
+    });
+    Ok(CreateEmbed::default()
+        .title(format!("Timeline for Report #{}", rid))
+        .description(if description.is_empty() {
+            "Nothing happened yet!".to_string()
+        } else {
+            description
+        })
+        .color(Color::BLURPLE))
+}
+
 pub async fn update_report_message(
     ctx: &impl CacheHttp,
     rid: u32,
@@ -204,7 +478,15 @@ pub async fn create_action_embed(
         .await
         .unwrap_or_else(|| u.global_name.as_ref().unwrap_or(&u.name).clone());
     //let ch = SL_AUDIT.to_channel(ctx).await.unwrap().g;
-    Ok(CreateEmbed::default()
+    let mut embed = CreateEmbed::default();
+    if let Some(t) = action
+        .created_at
+        .as_deref()
+        .and_then(|t| t.parse::<Timestamp>().ok())
+    {
+        embed = embed.timestamp(t);
+    }
+    Ok(embed
         .title(format!("Audit Log #{}", id))
         .color(Color::PURPLE /*from_rgb(249,19,109)*/)
         .author(CreateEmbedAuthor::new(nick).icon_url(u.face()))
@@ -216,6 +498,13 @@ pub async fn create_action_embed(
         )
         .field("Offense", do_sl_subs(&action.offense).into_owned(), false)
         .field("Action", do_sl_subs(&action.action).into_owned(), false)
+        .field(
+            "Punishment",
+            action
+                .punishment
+                .map_or_else(|| "Other".to_string(), |p| p.to_string()),
+            false,
+        )
         .footer(CreateEmbedFooter::new({
             if let Some(r) = action.report {
                 format!("/report report_id:{}", r)
@@ -233,6 +522,155 @@ pub fn create_action_components(id: u32) -> Vec<CreateActionRow> {
     .style(ButtonStyle::Secondary)])]
 }
 
+/// the lengths [`pick_punishment`] offers for timeouts and bans, besides whatever was guessed
+const TIMEOUT_LENGTHS: [u64; 3] = [10 * 60, 60 * 60, 24 * 60 * 60];
+const BAN_LENGTHS: [u64; 5] = [1, 3, 7, 14, 30];
+
+/// what a punishment is in [`pick_punishment`]'s menu
+fn punishment_value(p: Option<Punishment>) -> String {
+    match p.map(|p| p.to_db()) {
+        Some((kind, Some(secs))) => format!("{}:{}", kind, secs),
+        Some((kind, None)) => kind.to_string(),
+        None => "other".to_string(),
+    }
+}
+
+fn punishment_from_value(v: &str) -> Option<Punishment> {
+    let (kind, secs) = match v.split_once(':') {
+        Some((kind, secs)) => (kind, secs.parse().ok()),
+        None => (v, None),
+    };
+    Punishment::from_db(kind, secs)
+}
+
+fn punishment_menu(picked: Option<Punishment>) -> Vec<CreateActionRow> {
+    let mut choices = vec![Some(Punishment::Warn), Some(Punishment::Kick), Some(Punishment::Mute)];
+    choices.extend(
+        TIMEOUT_LENGTHS
+            .iter()
+            .map(|s| Some(Punishment::Timeout(Duration::from_secs(*s)))),
+    );
+    choices.extend(
+        BAN_LENGTHS
+            .iter()
+            .map(|d| Some(Punishment::Ban(Duration::from_secs(d * 24 * 60 * 60)))),
+    );
+    choices.extend([Some(Punishment::PermanentBan), None]);
+    // a length that isn't one of the usual ones
+    if !choices.contains(&picked) {
+        choices.insert(0, picked);
+    }
+    let options = choices
+        .into_iter()
+        .map(|p| {
+            CreateSelectMenuOption::new(
+                p.map_or_else(|| "Other".to_string(), |p| p.to_string()),
+                punishment_value(p),
+            )
+            .default_selection(p == picked)
+        })
+        .collect();
+    vec![
+        CreateActionRow::SelectMenu(CreateSelectMenu::new(
+            "punishment_pick",
+            CreateSelectMenuKind::String { options },
+        )),
+        CreateActionRow::Buttons(vec![CreateButton::new("punishment_save")
+            .label("Save")
+            .style(ButtonStyle::Primary)]),
+    ]
+}
+
+/// the messages with a [`pick_punishment`] menu that's still waiting for someone
+static OPEN_PICKERS: Mutex<BTreeSet<MessageId>> = Mutex::new(BTreeSet::new());
+
+/// whether `message` is a [`pick_punishment`] menu that's still waiting for someone
+pub fn picker_open(message: MessageId) -> bool {
+    OPEN_PICKERS
+        .lock()
+        .expect("pickers lock")
+        .contains(&message)
+}
+
+fn punishment_picked(picked: Option<Punishment>) -> String {
+    format!(
+        "Punishment: {}",
+        picked.map_or_else(|| "Other".to_string(), |p| p.to_string())
+    )
+}
+
+/// ask which punishment audit `id` was, after it's been saved with `guess`. the (ephemeral) menu
+/// is a followup to the interaction with `token`. if nobody presses save in time the menu goes
+/// away, and it's whatever was picked last. true if it changed
+pub async fn pick_punishment(
+    ctx: &Context,
+    db: &Database,
+    token: &str,
+    id: u32,
+    guess: Option<Punishment>,
+) -> anyhow::Result<bool> {
+    let msg = CreateInteractionResponseFollowup::new()
+        .content("What did they get?")
+        .components(punishment_menu(guess))
+        .ephemeral(true)
+        .execute(ctx, (None, token))
+        .await?;
+    OPEN_PICKERS
+        .lock()
+        .expect("pickers lock")
+        .insert(msg.id);
+    let picked = wait_for_pick(ctx, &msg, guess).await;
+    OPEN_PICKERS
+        .lock()
+        .expect("pickers lock")
+        .remove(&msg.id);
+    let (picked, saved) = picked?;
+    if !saved {
+        // so nobody clicks on a menu that isn't listening any more
+        CreateInteractionResponseFollowup::new()
+            .content(punishment_picked(picked))
+            .components(vec![])
+            .execute(ctx, (Some(msg.id), token))
+            .await?;
+    }
+    if picked == guess {
+        return Ok(false);
+    }
+    db.set_punishment(id, picked).await?;
+    Ok(true)
+}
+
+/// what was picked on `msg`'s menu, and whether save was pressed
+async fn wait_for_pick(
+    ctx: &Context,
+    msg: &Message,
+    mut picked: Option<Punishment>,
+) -> anyhow::Result<(Option<Punishment>, bool)> {
+    let mut interactions = ComponentInteractionCollector::new(&ctx.shard)
+        .message_id(msg.id)
+        .timeout(Duration::from_secs(120))
+        .stream();
+    while let Some(int) = interactions.next().await {
+        if let ComponentInteractionDataKind::StringSelect { values } = &int.data.kind {
+            picked = values.first().and_then(|v| punishment_from_value(v));
+            int.create_response(ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+            continue;
+        }
+        int.create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(punishment_picked(picked))
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+        return Ok((picked, true));
+    }
+    Ok((picked, false))
+}
+
 pub async fn update_audit_message(
     ctx: &impl CacheHttp,
     id: u32,
@@ -1,66 +1,298 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+mod cli;
 mod commands;
-use anyhow::Context as _;
+use anyhow::{bail, Context as _};
 use async_shutdown::ShutdownManager;
+use clap::Parser;
 use common::Action;
-use poise::serenity_prelude::{ChannelId, Client, GuildId};
+use poise::serenity_prelude::{ChannelId, Client, GuildId, ShardManager};
 use poise::{CreateReply, FrameworkError};
 use poise::{Framework, FrameworkOptions, Modal};
-use tracing::info;
+use tracing::{info, warn};
+use tracing_subscriber::{filter::LevelFilter, prelude::*, reload, Registry};
 mod tasks;
 use database::Database;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+mod backups;
 mod event;
-#[derive(Deserialize, Clone)]
+mod import;
+mod permissions;
+mod preflight;
+mod settings;
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Config {
     main: MainConfig,
+    #[serde(default)]
     secret_lab: SLConfig,
+    #[serde(default)]
     discord: DiscordConfig,
+    /// None if there is no `[permissions]` section, in which case everyone is a trial mod,
+    /// see [`permissions::UNCONFIGURED_TIER`]
+    permissions: Option<permissions::PermissionsConfig>,
+    #[serde(default)]
+    expiry: ExpiryConfig,
+    /// seconds between runs of each background task, see [`tasks::INTERVALS`]
+    #[serde(default)]
+    tasks: BTreeMap<String, u64>,
+    #[serde(default)]
+    backup: BackupConfig,
 }
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct MainConfig {
     token: String,
+    /// read the token from this file instead, like a docker secret
+    token_file: Option<PathBuf>,
 }
-#[derive(Deserialize, Clone)]
+/// the channels in here (and in [`DiscordConfig`]) are only used to set up the first guild,
+/// after that they live in the database and are changed with `/setup`
+#[derive(Deserialize, Serialize, Clone, Default)]
 pub struct SLConfig {
-    audit: ChannelId,
+    audit: Option<ChannelId>,
+    /// where the report webhook posts. channels lurk-chan has seen reports in are always checked too
+    #[serde(default)]
+    reports: Vec<ChannelId>,
 }
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, Default)]
 pub struct DiscordConfig {
-    reports: ChannelId,
-    audit: ChannelId,
-    stats: ChannelId,
+    reports: Option<ChannelId>,
+    audit: Option<ChannelId>,
+    stats: Option<ChannelId>,
     debug_guild: Option<GuildId>,
 }
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct ExpiryConfig {
+    /// release claims nobody has touched in this many hours. None to never release them
+    claim_idle_hours: Option<u64>,
+    /// hours before open reports from SL expire, instead of the guild's `report_expiry_hours`
+    sl_hours: Option<u64>,
+    /// the same, for reports from discord
+    discord_hours: Option<u64>,
+    /// hours for reports from each SL server, by name. beats `sl_hours`
+    #[serde(default)]
+    servers: BTreeMap<String, u64>,
+    /// expire claimed reports nothing has happened on for this many hours. None to never do that
+    claimed_hours: Option<u64>,
+    /// warn in the report channel this many hours before an open report expires
+    warn_hours: Option<u64>,
+}
+
+/// which backups to keep. the newest from each of the last `hourly` hours, `daily` days
+/// and `weekly` weeks stays, the rest are deleted
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    hourly: usize,
+    daily: usize,
+    weekly: usize,
+    /// compress backups with zstd
+    compress: bool,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            hourly: 24,
+            daily: 14,
+            weekly: 8,
+            compress: false,
+        }
+    }
+}
 
 pub const DEFAULT_CONFIG: &str = include_str!("../default_config.toml");
 
+pub const CONFIG_PATH: &str = "config.toml";
+/// environment variables starting with this override the config file.
+/// `LURK_DISCORD__AUDIT` is `audit` in `[discord]`
+const ENV_PREFIX: &str = "LURK_";
+
 fn load_or_create_config() -> anyhow::Result<Config> {
-    let config_path = PathBuf::from("config.toml");
-    if !config_path.exists() {
+    let config_path = PathBuf::from(CONFIG_PATH);
+    // if it's all in the environment there's no need for a file
+    let from_env = std::env::vars().any(|(k, _)| k.starts_with(ENV_PREFIX));
+    if !config_path.exists() && !from_env {
         std::fs::write(&config_path, DEFAULT_CONFIG).context("Failed to create default config")?;
     }
-    let config_file =
-        String::from_utf8(std::fs::read(config_path).context("failed to read config file")?)
-            .context("config file is not utf8!")?;
-    toml::from_str(&config_file).context("Failed to parse config file")
+    read_config()
+}
+
+/// config.toml, then environment variables on top, then the token file on top of that
+fn read_config() -> anyhow::Result<Config> {
+    let mut table: toml::Table = match std::fs::read(CONFIG_PATH) {
+        Ok(f) => toml::from_str(&String::from_utf8(f).context("config file is not utf8!")?)
+            .context("Failed to parse config file")?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(e).context("failed to read config file"),
+    };
+    apply_env(&mut table, std::env::vars())?;
+    read_token_file(&mut table)?;
+    let config: Config = table.try_into().context("Invalid config")?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// put every `LURK_SECTION__KEY` variable into the config. each `__` goes a table deeper, so
+/// `LURK_EXPIRY__SERVERS__LOBBY` is `lobby` under `[expiry.servers]`
+fn apply_env(
+    table: &mut toml::Table,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> anyhow::Result<()> {
+    for (name, value) in vars {
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let mut path: Vec<String> = path.split("__").map(str::to_lowercase).collect();
+        let key = path.pop().expect("split gives at least one");
+        if path.is_empty() {
+            continue;
+        }
+        // numbers and lists are toml, anything else is a string
+        let value = toml::from_str::<toml::Table>(&format!("v = {}", value))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .unwrap_or(toml::Value::String(value));
+        let mut t = &mut *table;
+        for section in path {
+            match t
+                .entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            {
+                toml::Value::Table(inner) => t = inner,
+                _ => bail!("{} doesn't point into a section", name),
+            }
+        }
+        t.insert(key, value);
+    }
+    Ok(())
+}
+
+/// if there's a `token_file`, the token in it beats `token`
+fn read_token_file(table: &mut toml::Table) -> anyhow::Result<()> {
+    let Some(toml::Value::Table(main)) = table.get_mut("main") else {
+        return Ok(());
+    };
+    let Some(path) = main.get("token_file") else {
+        return Ok(());
+    };
+    let path = path.as_str().context("token_file has to be a path")?;
+    let token = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the token from {}", path))?;
+    main.insert(
+        "token".to_string(),
+        toml::Value::String(token.trim().to_string()),
+    );
+    Ok(())
+}
+
+impl Config {
+    /// the config as toml, without anything secret in it
+    fn redacted(&self) -> anyhow::Result<String> {
+        let mut table = toml::Table::try_from(self)?;
+        if let Some(toml::Value::Table(main)) = table.get_mut("main") {
+            main.insert(
+                "token".to_string(),
+                toml::Value::String("<redacted>".to_string()),
+            );
+        }
+        Ok(toml::to_string_pretty(&table)?)
+    }
+    /// things toml can't check for us
+    fn validate(&self) -> anyhow::Result<()> {
+        poise::serenity_prelude::validate_token(&self.main.token).context("Invalid token")?;
+        if self.expiry.claim_idle_hours == Some(0) {
+            bail!("claim_idle_hours can't be 0, comment it out to never release claims");
+        }
+        let e = &self.expiry;
+        for (name, hours) in [
+            ("sl_hours", e.sl_hours),
+            ("discord_hours", e.discord_hours),
+            ("claimed_hours", e.claimed_hours),
+            ("warn_hours", e.warn_hours),
+        ]
+        .into_iter()
+        .chain(e.servers.iter().map(|(s, h)| (s.as_str(), Some(*h))))
+        {
+            if hours == Some(0) {
+                bail!("'{}' under [expiry] can't be 0 hours", name);
+            }
+        }
+        for (task, secs) in &self.tasks {
+            if !tasks::INTERVALS.iter().any(|(t, _)| t == task) {
+                bail!("There's no task called '{}' under [tasks]", task);
+            }
+            if *secs == 0 {
+                bail!("'{}' under [tasks] can't run every 0 seconds", task);
+            }
+        }
+        let b = &self.backup;
+        if b.hourly == 0 && b.daily == 0 && b.weekly == 0 {
+            bail!("[backup] has to keep some backups");
+        }
+        Ok(())
+    }
+}
+
+/// the config, shared by everything, swapped out whole when it's reloaded
+#[derive(Clone)]
+pub struct ConfigHandle(Arc<RwLock<Arc<Config>>>);
+
+impl ConfigHandle {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+    /// the config right now. don't hold on to it, or you won't see reloads
+    pub fn get(&self) -> Arc<Config> {
+        Arc::clone(&self.0.read().expect("config lock poisoned"))
+    }
+    /// read the config file again, and use it if it's valid. if it isn't the old one stays
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let new = read_config()?;
+        if new.main.token != self.get().main.token {
+            bail!("The token can't change without a restart");
+        }
+        *self.0.write().expect("config lock poisoned") = Arc::new(new);
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[command(version, about = "Lurk-chan, a discord bot for SCP:SL reports")]
+struct Args {
+    /// Print the config lurk-chan would use (with the token hidden) and exit
+    #[arg(long)]
+    print_config: bool,
+    #[command(subcommand)]
+    command: Option<cli::Cli>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
     color_backtrace::install();
-    tracing_subscriber::fmt::init();
-    info!("Hello, world!");
+    let log = setup_logging();
+    if let Some(command) = args.command {
+        return cli::run(command).await;
+    }
     // load config
     let config = load_or_create_config()?;
+    if args.print_config {
+        print!("{}", config.redacted()?);
+        return Ok(());
+    }
+    info!("Hello, world!");
     let shutdown = ShutdownManager::new();
-    // validate token
-    poise::serenity_prelude::validate_token(&config.main.token).context("Invalid token")?;
+    if config.permissions.is_none() {
+        warn!("No [permissions] in the config, everyone is a Trial Mod and only the bot's owner can use anything more until roles are set with /config!");
+    }
 
     setup_control_c(shutdown.clone());
 
-    bot(config, Database::new().await?, shutdown.clone()).await?;
+    if let Some(old) = backups::apply_staged().await? {
+        info!("The old database is at {}", old.display());
+    }
+    bot(config, Database::new().await?, shutdown.clone(), log).await?;
 
     let reason = shutdown.wait_shutdown_triggered().await;
     info!("Shutting down: {:?}", reason);
@@ -70,6 +302,23 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// changes how much gets logged while running
+pub type LogLevel = reload::Handle<LevelFilter, Registry>;
+
+/// log at the level in RUST_LOG (just the level, like `debug`), or info
+fn setup_logging() -> LogLevel {
+    let level = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(LevelFilter::INFO);
+    let (filter, handle) = reload::Layer::new(level);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+    handle
+}
+
 fn setup_control_c(s: ShutdownManager<&'static str>) {
     tokio::task::spawn(async move {
         tokio::signal::ctrl_c()
@@ -110,9 +359,12 @@ impl From<Action> for AuditModal {
     }
 }
 pub struct LurkChan {
-    pub config: Config,
+    pub config: ConfigHandle,
     pub db: Arc<Database>,
     pub shutdown: ShutdownManager<&'static str>,
+    pub shards: Arc<ShardManager>,
+    pub log: LogLevel,
+    pub tasks: tasks::Supervisor,
 }
 
 impl Clone for LurkChan {
@@ -121,11 +373,19 @@ impl Clone for LurkChan {
             config: self.config.clone(),
             db: Arc::clone(&self.db),
             shutdown: self.shutdown.clone(),
+            shards: Arc::clone(&self.shards),
+            log: self.log.clone(),
+            tasks: self.tasks.clone(),
         }
     }
 }
 
-async fn bot(config: Config, db: Database, s: ShutdownManager<&'static str>) -> anyhow::Result<()> {
+async fn bot(
+    config: Config,
+    db: Database,
+    s: ShutdownManager<&'static str>,
+    log: LogLevel,
+) -> anyhow::Result<()> {
     let framework_shutdown = s.clone();
     //let background_shutdown = s.clone();
     use poise::serenity_prelude::GatewayIntents;
@@ -136,6 +396,7 @@ async fn bot(config: Config, db: Database, s: ShutdownManager<&'static str>) ->
     .framework(Framework::new(
         FrameworkOptions {
             commands: commands::commands(),
+            command_check: Some(|ctx| Box::pin(permissions::check_command(ctx))),
             event_handler: |framework, evt| {
                 Box::pin(async move {
                     if let Err(e) = event::handle(framework.serenity_context, evt, framework).await {
@@ -185,9 +446,12 @@ async fn bot(config: Config, db: Database, s: ShutdownManager<&'static str>) ->
                     .await
                     .context("failed to register commands")?;
                 let lc = LurkChan {
-                    config,
+                    config: ConfigHandle::new(config),
                     db: Arc::new(db),
                     shutdown: framework_shutdown,
+                    shards: Arc::clone(framework.shard_manager()),
+                    log,
+                    tasks: Default::default(),
                 };
                 Ok(lc)
             })
@@ -220,10 +484,44 @@ async fn bot(config: Config, db: Database, s: ShutdownManager<&'static str>) ->
 
 #[cfg(test)]
 mod tests {
-    use crate::{Config, DEFAULT_CONFIG};
+    use crate::{apply_env, Config, DEFAULT_CONFIG};
 
     #[test]
     fn default_config_parses() {
         let _: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
     }
+
+    #[test]
+    fn env_overrides_config() {
+        let mut table: toml::Table = toml::from_str(DEFAULT_CONFIG).unwrap();
+        let vars = [
+            ("LURK_DISCORD__AUDIT", "42"),
+            ("LURK_MAIN__TOKEN", "abc.def"),
+            ("LURK_PERMISSIONS__MOD", "[1, 2]"),
+            ("LURK_EXPIRY__CLAIM_IDLE_HOURS", "12"),
+            ("LURK_EXPIRY__SERVERS__LOBBY", "6"),
+            ("LURKING", "not ours"),
+        ];
+        apply_env(
+            &mut table,
+            vars.map(|(k, v)| (k.to_string(), v.to_string())),
+        )
+        .unwrap();
+        let c: Config = table.try_into().unwrap();
+        assert_eq!(c.discord.audit.map(|c| c.get()), Some(42));
+        assert_eq!(c.main.token, "abc.def");
+        assert_eq!(c.expiry.claim_idle_hours, Some(12));
+        assert_eq!(c.expiry.servers.get("lobby"), Some(&6));
+        assert_eq!(
+            c.permissions.unwrap().roles(crate::permissions::Tier::Mod).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn env_goes_into_nested_tables() {
+        let mut table = toml::Table::new();
+        apply_env(&mut table, [("LURK_A__B__C".to_string(), "1".to_string())]).unwrap();
+        assert_eq!(table["a"]["b"]["c"].as_integer(), Some(1));
+    }
 }
@@ -0,0 +1,201 @@
+use std::fmt::Display;
+
+use poise::serenity_prelude::{
+    CacheHttp, ComponentInteraction, CreateInteractionResponse, CreateInteractionResponseMessage,
+    Member, RoleId,
+};
+use poise::CreateReply;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{settings::Settings, Context};
+
+/// staff tiers, lowest first. every tier can do everything the tiers below it can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    Everyone,
+    TrialMod,
+    Mod,
+    Admin,
+    Owner,
+}
+
+impl Display for Tier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Tier::Everyone => "Everyone",
+            Tier::TrialMod => "Trial Mod",
+            Tier::Mod => "Mod",
+            Tier::Admin => "Admin",
+            Tier::Owner => "Owner",
+        })
+    }
+}
+
+/// which roles belong to which tier
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct PermissionsConfig {
+    #[serde(default)]
+    trial_mod: Vec<RoleId>,
+    #[serde(default, rename = "mod")]
+    moderator: Vec<RoleId>,
+    #[serde(default)]
+    admin: Vec<RoleId>,
+    #[serde(default)]
+    owner: Vec<RoleId>,
+}
+
+impl PermissionsConfig {
+    /// the roles in `tier`. [`Tier::Everyone`] doesn't have any
+    pub fn roles(&self, tier: Tier) -> &[RoleId] {
+        match tier {
+            Tier::Everyone => &[],
+            Tier::TrialMod => &self.trial_mod,
+            Tier::Mod => &self.moderator,
+            Tier::Admin => &self.admin,
+            Tier::Owner => &self.owner,
+        }
+    }
+    pub fn set_roles(&mut self, tier: Tier, roles: Vec<RoleId>) {
+        match tier {
+            Tier::Everyone => {}
+            Tier::TrialMod => self.trial_mod = roles,
+            Tier::Mod => self.moderator = roles,
+            Tier::Admin => self.admin = roles,
+            Tier::Owner => self.owner = roles,
+        }
+    }
+    fn tier_of(&self, roles: &[RoleId]) -> Tier {
+        let has = |tier_roles: &[RoleId]| roles.iter().any(|r| tier_roles.contains(r));
+        if has(&self.owner) {
+            Tier::Owner
+        } else if has(&self.admin) {
+            Tier::Admin
+        } else if has(&self.moderator) {
+            Tier::Mod
+        } else if has(&self.trial_mod) {
+            Tier::TrialMod
+        } else {
+            Tier::Everyone
+        }
+    }
+}
+
+/// what everyone gets while no roles are set up: the staff buttons and lookups, but nothing that
+/// changes how lurk-chan is set up. the bot's owners skip the checks, so they can still /config roles
+pub const UNCONFIGURED_TIER: Tier = Tier::TrialMod;
+
+/// the highest tier someone has. without any roles set up, [`UNCONFIGURED_TIER`]
+pub fn tier_of(roles: Option<&PermissionsConfig>, member: Option<&Member>) -> Tier {
+    match (roles, member) {
+        (None, _) => UNCONFIGURED_TIER,
+        (Some(p), Some(m)) => p.tier_of(&m.roles),
+        (Some(_), None) => Tier::Everyone,
+    }
+}
+
+/// what tier each kind of button needs
+pub fn button_tier(kind: &str) -> Tier {
+    match kind {
+        "claim" | "close" | "unclaim" | "reassign" | "edit" | "past" | "timeline"
+        | "leaderboard" => Tier::TrialMod,
+        "forceclose" | "reopen" => Tier::Mod,
+        _ => Tier::Owner,
+    }
+}
+
+/// can `tier` act on something that belongs to someone else? (closing someone else's claim, editing their audit, etc)
+pub fn can_override_claimant(tier: Tier) -> bool {
+    tier >= Tier::Admin
+}
+
+fn denied_message(needed: Tier, configured: bool) -> String {
+    if configured {
+        format!("You need to be {} or higher to do that!", needed)
+    } else {
+        format!(
+            "You need to be {} or higher to do that, and nobody is until roles are set up! Configure [permissions] in the config, or the roles with /config",
+            needed
+        )
+    }
+}
+
+/// tell someone they can't press that button
+pub async fn deny_button(
+    ctx: impl CacheHttp,
+    int: &ComponentInteraction,
+    have: Tier,
+    needed: Tier,
+    configured: bool,
+) -> anyhow::Result<()> {
+    warn!(
+        "Denied {} ({}, {}) the '{}' button, needs {}",
+        int.user.name, int.user.id, have, int.data.custom_id, needed
+    );
+    int.create_response(
+        ctx,
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::default()
+                .content(denied_message(needed, configured))
+                .ephemeral(true),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// the `command_check` for every command. the tier a command needs is stored in its `custom_data`
+pub async fn check_command(ctx: Context<'_>) -> anyhow::Result<bool> {
+    let needed = ctx
+        .command()
+        .custom_data
+        .downcast_ref::<Tier>()
+        .copied()
+        .unwrap_or(Tier::Owner);
+    let settings = Settings::for_guild(ctx.data(), ctx.guild_id()).await?;
+    let have = tier_of(
+        settings.roles.as_ref(),
+        ctx.author_member().await.as_deref(),
+    );
+    if have >= needed {
+        return Ok(true);
+    }
+    warn!(
+        "Denied {} ({}, {}) the '/{}' command, needs {}",
+        ctx.author().name,
+        ctx.author().id,
+        have,
+        ctx.command().qualified_name,
+        needed
+    );
+    ctx.send(
+        CreateReply::default()
+            .content(denied_message(needed, settings.roles.is_some()))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tier_of, PermissionsConfig, Tier, UNCONFIGURED_TIER};
+    use poise::serenity_prelude::RoleId;
+
+    #[test]
+    fn highest_tier_wins() {
+        let p: PermissionsConfig =
+            toml::from_str("trial_mod = [1]\nmod = [2]\nadmin = [3]").unwrap();
+        assert_eq!(p.tier_of(&[]), Tier::Everyone);
+        assert_eq!(p.tier_of(&[RoleId::new(1)]), Tier::TrialMod);
+        assert_eq!(p.tier_of(&[RoleId::new(1), RoleId::new(3)]), Tier::Admin);
+        assert_eq!(p.tier_of(&[RoleId::new(9), RoleId::new(2)]), Tier::Mod);
+        assert!(Tier::Owner > Tier::Admin && Tier::TrialMod > Tier::Everyone);
+    }
+
+    #[test]
+    fn no_roles_is_not_an_admin() {
+        assert_eq!(tier_of(None, None), UNCONFIGURED_TIER);
+        assert!(UNCONFIGURED_TIER < Tier::Admin);
+    }
+}
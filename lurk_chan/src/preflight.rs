@@ -0,0 +1,183 @@
+use std::fmt::Display;
+
+use poise::serenity_prelude::{ApplicationFlags, ChannelId, Context, GuildId, Permissions};
+use tracing::{error, info};
+
+use crate::LurkChan;
+
+/// what a channel is for, which decides what we need to be able to do in it
+#[derive(Debug, Clone, Copy)]
+enum Purpose {
+    SlAudit,
+    DiscordAudit,
+    DiscordReports,
+    Stats,
+    SlReports,
+}
+
+impl Purpose {
+    fn needs(self) -> Permissions {
+        let p = Permissions::VIEW_CHANNEL
+            | Permissions::SEND_MESSAGES
+            | Permissions::EMBED_LINKS
+            | Permissions::READ_MESSAGE_HISTORY;
+        match self {
+            // the webhook's messages get deleted once they're turned into reports
+            Purpose::SlReports => p | Permissions::MANAGE_MESSAGES,
+            _ => p,
+        }
+    }
+}
+
+impl Display for Purpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Purpose::SlAudit => "SL audit channel",
+            Purpose::DiscordAudit => "Discord audit channel",
+            Purpose::DiscordReports => "Discord reports channel",
+            Purpose::Stats => "Stats channel",
+            Purpose::SlReports => "SL reports channel",
+        })
+    }
+}
+
+/// one thing the preflight looked at
+pub struct Check {
+    /// the guild it's about. None for things about the whole bot
+    pub guild: Option<GuildId>,
+    pub what: String,
+    /// what's wrong with it, None if nothing
+    pub problem: Option<String>,
+}
+
+/// make sure lurk-chan can actually do its job: every channel exists, is a text channel
+/// in a guild we're in, and we have the permissions we need there
+pub async fn run(ctx: &Context, lc: &LurkChan) -> anyhow::Result<Vec<Check>> {
+    let mut checks = vec![];
+
+    let flags = ctx.http.get_current_application_info().await?.flags;
+    let content = flags.is_some_and(|f| {
+        f.intersects(
+            ApplicationFlags::GATEWAY_MESSAGE_CONTENT
+                | ApplicationFlags::GATEWAY_MESSAGE_CONTENT_LIMITED,
+        )
+    });
+    checks.push(Check {
+        guild: None,
+        what: "Message Content intent".to_string(),
+        problem: (!content)
+            .then(|| "not enabled in the developer portal, reports can't be read".to_string()),
+    });
+
+    for settings in lc.db.all_guild_settings().await? {
+        let guild = GuildId::new(settings.guild_id);
+        if ctx.cache.guild(guild).is_none() {
+            checks.push(Check {
+                guild: Some(guild),
+                what: format!("Server {}", guild),
+                problem: Some("lurk-chan isn't in it".to_string()),
+            });
+            continue;
+        }
+        for (purpose, channel, required) in [
+            (Purpose::SlAudit, settings.sl_audit, true),
+            (Purpose::DiscordAudit, settings.discord_audit, true),
+            (Purpose::DiscordReports, settings.discord_reports, false),
+            (Purpose::Stats, settings.stats, false),
+        ] {
+            let problem = match channel {
+                Some(c) => check_channel(ctx, ChannelId::new(c), Some(guild), purpose)
+                    .await
+                    .err(),
+                // audits have to go somewhere, the rest is optional
+                None if required => Some("not set, use /setup or /config".to_string()),
+                None => continue,
+            };
+            checks.push(Check {
+                guild: Some(guild),
+                what: match channel {
+                    Some(c) => format!("{} <#{}>", purpose, c),
+                    None => purpose.to_string(),
+                },
+                problem,
+            });
+        }
+    }
+
+    for channel in lc.config.get().secret_lab.reports.iter().copied() {
+        let (guild, problem) = match check_channel(ctx, channel, None, Purpose::SlReports).await {
+            Ok(g) => (Some(g), None),
+            Err(e) => (None, Some(e)),
+        };
+        checks.push(Check {
+            guild,
+            what: format!("{} <#{}>", Purpose::SlReports, channel),
+            problem,
+        });
+    }
+    Ok(checks)
+}
+
+/// the guild it's in if everything is fine, what's wrong if it isn't
+async fn check_channel(
+    ctx: &Context,
+    channel: ChannelId,
+    expected: Option<GuildId>,
+    purpose: Purpose,
+) -> Result<GuildId, String> {
+    let c = match channel.to_channel(ctx).await {
+        Ok(c) => c,
+        Err(e) => return Err(format!("can't see it ({})", e)),
+    };
+    let Some(c) = c.guild() else {
+        return Err("isn't in a server".to_string());
+    };
+    if expected.is_some_and(|g| g != c.guild_id) {
+        return Err("is in a different server".to_string());
+    }
+    if !c.is_text_based() {
+        return Err("isn't a text channel".to_string());
+    }
+    let me = ctx.cache.current_user().id;
+    let member = c
+        .guild_id
+        .member(ctx, me)
+        .await
+        .map_err(|_| "lurk-chan isn't in its server".to_string())?;
+    let have = {
+        let Some(guild) = ctx.cache.guild(c.guild_id) else {
+            return Err("lurk-chan isn't in its server".to_string());
+        };
+        guild.user_permissions_in(&c, &member)
+    };
+    let missing = purpose.needs() - have;
+    if !missing.is_empty() {
+        return Err(format!(
+            "missing permissions: {}",
+            missing.get_permission_names().join(", ")
+        ));
+    }
+    Ok(c.guild_id)
+}
+
+/// log every check, loudly if something is wrong
+pub fn log(checks: &[Check]) {
+    let mut problems = 0;
+    for c in checks {
+        match &c.problem {
+            None => info!("Preflight: {} is OK", c.what),
+            Some(p) => {
+                problems += 1;
+                error!("Preflight: {} {}", c.what, p);
+            }
+        }
+    }
+    if problems > 0 {
+        error!(
+            "Preflight found {} problem(s)! Things WILL break until they're fixed, see /diagnostics",
+            problems
+        );
+    } else {
+        info!("Preflight passed");
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::Context as _;
+use database::GuildSettings;
+use poise::serenity_prelude::{CacheHttp, ChannelId, GuildId, RoleId};
+use poise::ChoiceParameter as _;
+
+use crate::{
+    permissions::{PermissionsConfig, Tier},
+    Config, LurkChan,
+};
+
+/// open reports expire after this many hours, unless a guild says otherwise
+pub const DEFAULT_REPORT_EXPIRY_HOURS: u64 = 48;
+/// never keep a report open (or a claim idle) longer than a month
+const MAX_HOURS: u64 = 30 * 24;
+/// a leaderboard page has to fit in an embed
+const MAX_LEADERBOARD_SIZE: u32 = 50;
+
+/// everything `/config` can change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Key {
+    #[name = "sl_audit"]
+    SlAudit,
+    #[name = "discord_audit"]
+    DiscordAudit,
+    #[name = "discord_reports"]
+    DiscordReports,
+    #[name = "stats"]
+    Stats,
+    #[name = "report_expiry_hours"]
+    ReportExpiryHours,
+    #[name = "claim_idle_hours"]
+    ClaimIdleHours,
+    #[name = "leaderboard_size"]
+    LeaderboardSize,
+    #[name = "trial_mod_roles"]
+    TrialModRoles,
+    #[name = "mod_roles"]
+    ModRoles,
+    #[name = "admin_roles"]
+    AdminRoles,
+    #[name = "owner_roles"]
+    OwnerRoles,
+}
+
+impl Key {
+    pub const ALL: [Key; 11] = [
+        Key::SlAudit,
+        Key::DiscordAudit,
+        Key::DiscordReports,
+        Key::Stats,
+        Key::ReportExpiryHours,
+        Key::ClaimIdleHours,
+        Key::LeaderboardSize,
+        Key::TrialModRoles,
+        Key::ModRoles,
+        Key::AdminRoles,
+        Key::OwnerRoles,
+    ];
+    /// what it does, and what it takes
+    pub fn describe(self) -> &'static str {
+        match self {
+            Key::SlAudit => "Channel SL audits go in",
+            Key::DiscordAudit => "Channel discord audits go in",
+            Key::DiscordReports => "Channel 'Report Message to Staff' reports go in",
+            Key::Stats => "Channel the stats message lives in",
+            Key::ReportExpiryHours => "Hours before an open report expires",
+            Key::ClaimIdleHours => "Hours before an idle claim is released, or `never`",
+            Key::LeaderboardSize => "People on a page of the leaderboard",
+            Key::TrialModRoles => "Trial mod roles, or `none`",
+            Key::ModRoles => "Mod roles, or `none`",
+            Key::AdminRoles => "Admin roles, or `none`",
+            Key::OwnerRoles => "Owner roles, or `none`",
+        }
+    }
+    /// the tier a role key is for
+    pub fn tier(self) -> Option<Tier> {
+        match self {
+            Key::TrialModRoles => Some(Tier::TrialMod),
+            Key::ModRoles => Some(Tier::Mod),
+            Key::AdminRoles => Some(Tier::Admin),
+            Key::OwnerRoles => Some(Tier::Owner),
+            _ => None,
+        }
+    }
+    pub fn is_channel(self) -> bool {
+        matches!(
+            self,
+            Key::SlAudit | Key::DiscordAudit | Key::DiscordReports | Key::Stats
+        )
+    }
+    /// channels are kept with the rest of the guild's channels, not in the settings table
+    pub fn channel(self, channels: &mut GuildSettings) -> Option<&mut Option<u64>> {
+        match self {
+            Key::SlAudit => Some(&mut channels.sl_audit),
+            Key::DiscordAudit => Some(&mut channels.discord_audit),
+            Key::DiscordReports => Some(&mut channels.discord_reports),
+            Key::Stats => Some(&mut channels.stats),
+            _ => None,
+        }
+    }
+}
+
+/// a guild's settings, with anything it didn't set taken from the config
+#[derive(Clone)]
+pub struct Settings {
+    pub channels: GuildSettings,
+    pub report_expiry_hours: u64,
+    /// None to never release claims
+    pub claim_idle_hours: Option<u64>,
+    pub leaderboard_size: u32,
+    /// None if no roles are set up anywhere, in which case everyone is a trial mod
+    pub roles: Option<PermissionsConfig>,
+}
+
+impl Settings {
+    /// what a guild gets before it changes anything
+    pub fn defaults(config: &Config) -> Self {
+        Self {
+            channels: GuildSettings::default(),
+            report_expiry_hours: DEFAULT_REPORT_EXPIRY_HOURS,
+            claim_idle_hours: config.expiry.claim_idle_hours,
+            leaderboard_size: lurk_chan::LEADERBOARD_PAGE_SIZE,
+            roles: config.permissions.clone(),
+        }
+    }
+    pub async fn load(lc: &LurkChan, guild: u64) -> anyhow::Result<Self> {
+        let channels = lc.db.guild_settings(guild).await?.unwrap_or(GuildSettings {
+            guild_id: guild,
+            ..Default::default()
+        });
+        Self::from_stored(&lc.config.get(), channels, &lc.db.settings(guild).await?)
+    }
+    /// the defaults, with what's in the settings table on top
+    pub fn from_stored(
+        config: &Config,
+        channels: GuildSettings,
+        stored: &[(String, String)],
+    ) -> anyhow::Result<Self> {
+        let mut s = Self::defaults(config);
+        s.channels = channels;
+        for (key, value) in stored {
+            // a key from a newer (or older) lurk-chan
+            let Some(key) = Key::from_name(key) else {
+                continue;
+            };
+            s.apply(key, value)
+                .with_context(|| format!("Bad value for {}", key.name()))?;
+        }
+        Ok(s)
+    }
+    /// [`Settings::load`], or the defaults outside of a guild
+    pub async fn for_guild(lc: &LurkChan, guild: Option<GuildId>) -> anyhow::Result<Self> {
+        match guild {
+            Some(g) => Self::load(lc, g.get()).await,
+            None => Ok(Self::defaults(&lc.config.get())),
+        }
+    }
+    /// use a value, the way [`parse`] turned it into a string
+    pub fn apply(&mut self, key: Key, value: &str) -> anyhow::Result<()> {
+        match key {
+            Key::ReportExpiryHours => self.report_expiry_hours = value.parse()?,
+            Key::ClaimIdleHours => {
+                self.claim_idle_hours = match value {
+                    "never" => None,
+                    v => Some(v.parse()?),
+                }
+            }
+            Key::LeaderboardSize => self.leaderboard_size = value.parse()?,
+            Key::TrialModRoles | Key::ModRoles | Key::AdminRoles | Key::OwnerRoles => {
+                let roles = value
+                    .split(',')
+                    .filter(|r| !r.is_empty())
+                    .map(|r| Ok(RoleId::new(r.parse()?)))
+                    .collect::<anyhow::Result<_>>()?;
+                self.roles
+                    .get_or_insert_with(Default::default)
+                    .set_roles(key.tier().expect("role key"), roles);
+            }
+            Key::SlAudit | Key::DiscordAudit | Key::DiscordReports | Key::Stats => {
+                let channel = key.channel(&mut self.channels).expect("channel key");
+                *channel = Some(value.parse()?);
+            }
+        }
+        Ok(())
+    }
+    /// how `key` is set right now, for people
+    pub fn show(&self, key: Key) -> String {
+        let mut channels = self.channels.clone();
+        if let Some(c) = key.channel(&mut channels) {
+            return match c {
+                Some(c) => format!("<#{}>", c),
+                None => "Not set".to_string(),
+            };
+        }
+        match key {
+            Key::ReportExpiryHours => format!("{} hours", self.report_expiry_hours),
+            Key::ClaimIdleHours => match self.claim_idle_hours {
+                Some(h) => format!("{} hours", h),
+                None => "Never".to_string(),
+            },
+            Key::LeaderboardSize => self.leaderboard_size.to_string(),
+            _ => {
+                let tier = key.tier().expect("role key");
+                match &self.roles {
+                    None => "Not set up, everyone is a Trial Mod".to_string(),
+                    Some(r) if r.roles(tier).is_empty() => "None".to_string(),
+                    Some(r) => r
+                        .roles(tier)
+                        .iter()
+                        .map(|r| format!("<@&{}>", r))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                }
+            }
+        }
+    }
+}
+
+/// check `input` makes sense for `key` in `guild`, and turn it into what gets stored.
+/// the error is for whoever typed it
+pub async fn parse(
+    ctx: impl CacheHttp,
+    guild: GuildId,
+    key: Key,
+    input: &str,
+) -> Result<String, String> {
+    let input = input.trim();
+    match key {
+        Key::SlAudit | Key::DiscordAudit | Key::DiscordReports | Key::Stats => {
+            let id = mention_id(input, "<#", ">").ok_or("That isn't a channel!")?;
+            let channel = ChannelId::new(id)
+                .to_channel(&ctx)
+                .await
+                .map_err(|_| "I can't see that channel!")?;
+            match channel.guild() {
+                Some(c) if c.guild_id == guild && c.is_text_based() => Ok(id.to_string()),
+                _ => Err("That has to be a text channel in this server!".to_string()),
+            }
+        }
+        Key::ReportExpiryHours => hours(input),
+        Key::ClaimIdleHours => match input.to_lowercase().as_str() {
+            "never" | "off" => Ok("never".to_string()),
+            _ => hours(input),
+        },
+        Key::LeaderboardSize => match input.parse::<u32>() {
+            Ok(n) if (1..=MAX_LEADERBOARD_SIZE).contains(&n) => Ok(n.to_string()),
+            _ => Err(format!(
+                "That has to be a number from 1 to {}!",
+                MAX_LEADERBOARD_SIZE
+            )),
+        },
+        Key::TrialModRoles | Key::ModRoles | Key::AdminRoles | Key::OwnerRoles => {
+            if input.eq_ignore_ascii_case("none") {
+                return Ok(String::new());
+            }
+            let existing = guild
+                .roles(ctx.http())
+                .await
+                .map_err(|_| "I can't see this server's roles!")?;
+            let mut ids = vec![];
+            for r in input.split(|c: char| c == ',' || c.is_whitespace()) {
+                if r.is_empty() {
+                    continue;
+                }
+                let id =
+                    mention_id(r, "<@&", ">").ok_or_else(|| format!("`{}` isn't a role!", r))?;
+                if !existing.contains_key(&RoleId::new(id)) {
+                    return Err(format!("<@&{}> isn't a role in this server!", id));
+                }
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+            if ids.is_empty() {
+                return Err("Give me some roles, or `none`!".to_string());
+            }
+            Ok(ids
+                .into_iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(","))
+        }
+    }
+}
+
+fn hours(input: &str) -> Result<String, String> {
+    match input.parse::<u64>() {
+        Ok(n) if (1..=MAX_HOURS).contains(&n) => Ok(n.to_string()),
+        _ => Err(format!(
+            "That has to be a number of hours from 1 to {}!",
+            MAX_HOURS
+        )),
+    }
+}
+
+/// `123` or `<#123>` (with the right prefix) to 123
+fn mention_id(input: &str, prefix: &str, suffix: &str) -> Option<u64> {
+    let id = input
+        .strip_prefix(prefix)
+        .and_then(|i| i.strip_suffix(suffix))
+        .unwrap_or(input);
+    id.parse().ok().filter(|i| *i != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mention_id;
+
+    #[test]
+    fn mentions() {
+        assert_eq!(mention_id("<#123>", "<#", ">"), Some(123));
+        assert_eq!(mention_id("123", "<#", ">"), Some(123));
+        assert_eq!(mention_id("<@&45>", "<@&", ">"), Some(45));
+        assert_eq!(mention_id("<@&45>", "<#", ">"), None);
+        assert_eq!(mention_id("0", "<#", ">"), None);
+        assert_eq!(mention_id("general", "<#", ">"), None);
+    }
+}
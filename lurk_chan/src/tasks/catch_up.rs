@@ -0,0 +1,70 @@
+use async_shutdown::ShutdownManager;
+use poise::serenity_prelude::{
+    futures::{StreamExt as _, TryStreamExt as _},
+    CacheHttp, ChannelId,
+};
+use tracing::{info, instrument, warn};
+
+use crate::{event, LurkChan};
+
+/// never look further back than this many messages in one channel
+const MAX_SCAN: usize = 1000;
+
+/// picks up reports the webhook sent while we were offline
+#[instrument(skip(ctx, lc, shut))]
+pub async fn catch_up_task(
+    ctx: impl CacheHttp,
+    lc: LurkChan,
+    shut: ShutdownManager<&'static str>,
+) -> anyhow::Result<()> {
+    let mut channels = lc.config.get().secret_lab.reports.clone();
+    for c in lc.db.report_channels().await? {
+        let c = ChannelId::new(c);
+        if !channels.contains(&c) {
+            channels.push(c);
+        }
+    }
+    for channel in channels {
+        if shut.is_shutdown_triggered() {
+            break;
+        }
+        match catch_up_channel(&ctx, &lc, channel).await {
+            Ok(0) => {}
+            Ok(n) => info!("Caught up on {} missed reports in {}", n, channel),
+            Err(e) => warn!("Failed to catch up on reports in {}: {}", channel, e),
+        }
+    }
+    lc.tasks.ran("catch_up");
+    Ok(())
+}
+
+async fn catch_up_channel(
+    ctx: &impl CacheHttp,
+    lc: &LurkChan,
+    channel: ChannelId,
+) -> anyhow::Result<usize> {
+    let last = lc.db.last_source_message(channel.get()).await?;
+    let mut messages = channel.messages_iter(ctx.http()).boxed();
+    let mut missed = vec![];
+    let mut scanned = 0;
+    // newest first, back to the last report we know about
+    while let Some(m) = messages.try_next().await? {
+        if last.is_some_and(|l| m.id.get() <= l) || scanned >= MAX_SCAN {
+            break;
+        }
+        scanned += 1;
+        if matches!(event::report_from_msg(&m), Ok(Some(_))) {
+            missed.push(m);
+        }
+    }
+    let mut done = 0;
+    // oldest first, like they would have come in
+    for m in missed.iter().rev() {
+        match event::ingest_report(ctx, m, lc).await {
+            Ok(true) => done += 1,
+            Ok(false) => {}
+            Err(e) => warn!("Failed to ingest missed report {}: {}", m.id, e),
+        }
+    }
+    Ok(done)
+}
@@ -1,4 +1,4 @@
-use std::io::{stdin, IsTerminal};
+use std::io::{stdin, IsTerminal, Write as _};
 use std::thread;
 use std::time::Duration;
 
@@ -44,12 +44,12 @@ fn console_thread(tx: UnboundedSender<String>) {
         }
     }
 }
-#[instrument(skip(s, rx))]
+#[instrument(skip(s, rx, lc, ctx))]
 async fn console_process(
     s: ShutdownManager<&'static str>,
     mut rx: UnboundedReceiver<String>,
-    _: LurkChan,
-    _: impl CacheHttp,
+    lc: LurkChan,
+    ctx: impl CacheHttp,
 ) {
     loop {
         tokio::select! {
@@ -71,6 +71,19 @@ async fn console_process(
                     Ok(Commands::Quit) => {
                         let _ = s.trigger_shutdown("Console request");
                     },
+                    Ok(Commands::Reload) => reload(&lc, "console"),
+                    Ok(Commands::Reconcile) => {
+                        info!("Reconciling messages, this might take a bit");
+                        match reconcile(&ctx, &lc).await {
+                            Ok(r) => log_reconciled(&r),
+                            Err(e) => error!("Failed to reconcile messages: {}", e),
+                        }
+                    },
+                    Ok(c) => {
+                        if let Err(e) = run(c, &ctx, &lc).await {
+                            error!("{:#}", e);
+                        }
+                    }
                     Err(e) => {
                         let is_err = e.use_stderr();
                         let e = e.render();
@@ -82,8 +95,16 @@ async fn console_process(
     }
 }
 
-use clap::{Parser, Subcommand};
+use anyhow::Context as _;
+use clap::{Parser, Subcommand, ValueEnum};
+use common::{Action, Report};
+use database::{Outcome, DB_FILE};
+use lurk_chan::{update_audit_message, update_report_message};
+use tracing::{level_filters::LevelFilter, warn};
 
+use super::expire;
+use super::reconcile::{log_reconciled, reconcile};
+use super::reload::reload;
 use crate::LurkChan;
 
 #[derive(Parser, Debug)]
@@ -96,4 +117,271 @@ struct Command {
 enum Commands {
     /// Exits the bot
     Quit,
+    /// Posts deleted report/audit messages again and lists messages the database doesn't know about
+    Reconcile,
+    /// Reads config.toml again
+    Reload,
+    /// Shows shards, latency, background tasks, and how big the database is
+    Status,
+    /// Shows a report
+    Report { id: u32 },
+    /// Shows an audit
+    Audit { id: u32 },
+    /// Shows what a player has been up to
+    Player {
+        /// Steam/discord id of the player
+        id: String,
+        /// Only this server (default: all of them)
+        #[arg(long)]
+        guild: Option<u64>,
+    },
+    /// Expires a report, or runs the expiry pass if no id is given
+    Expire { id: Option<u32> },
+    /// Closes a claimed report without an audit
+    Close { id: u32 },
+    /// Reopens a closed report
+    Reopen { id: u32 },
+    /// Backs the database up now
+    Backup,
+    /// Edits a report or audit message to match the database
+    Render { what: Render, id: u32 },
+    /// Checks the database for corruption
+    Integrity,
+    /// Lists reports expiry is leaving alone because they're broken
+    Quarantine {
+        /// Let expiry look at this report again, once it's fixed
+        #[arg(long)]
+        release: Option<u32>,
+    },
+    /// Changes how much gets logged (error, warn, info, debug, trace or off)
+    Log { level: LevelFilter },
+    /// Writes reports, audits or audit edits to a file (in exports/ unless --out says otherwise)
+    Export(crate::cli::ExportArgs),
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Render {
+    Report,
+    Audit,
+}
+
+/// everything that isn't handled right in [`console_process`]
+async fn run(command: Commands, ctx: &impl CacheHttp, lc: &LurkChan) -> anyhow::Result<()> {
+    // things done from the console are done by lurk-chan
+    let me = ctx.cache().context("No cache")?.current_user().id.get();
+    match command {
+        Commands::Quit | Commands::Reconcile | Commands::Reload => {}
+        Commands::Status => {
+            for (id, shard) in lc.shards.runners.lock().await.iter() {
+                info!(
+                    "Shard {}: {}, latency {}",
+                    id,
+                    shard.stage,
+                    shard
+                        .latency
+                        .map(|l| format!("{}ms", l.as_millis()))
+                        .unwrap_or_else(|| "unknown".to_string())
+                );
+            }
+            let size = ["", "-wal"]
+                .iter()
+                .filter_map(|s| std::fs::metadata(format!("{}{}", DB_FILE, s)).ok())
+                .map(|m| m.len())
+                .sum::<u64>();
+            info!("Database: {:.1} MiB", size as f64 / (1024.0 * 1024.0));
+            for (name, h) in lc.tasks.health() {
+                info!("Task {}: {}", name, h.summary());
+            }
+            info!(
+                "Reports: {}, audits: {}, waiting in the outbox: {}",
+                lc.db.total_report_count().await?,
+                lc.db.total_action_count().await?,
+                lc.db.outbox_count().await?
+            );
+        }
+        Commands::Report { id } => {
+            let report = lc
+                .db
+                .get_report_from_id(id)
+                .await?
+                .context("No such report")?;
+            log_report(id, &report);
+            if let Some(source) = lc.db.report_import_source(id).await? {
+                info!("  Imported from {}", source);
+            }
+            for e in lc.db.report_events(id).await? {
+                info!(
+                    "  {} {:?} -> {:?} by {} {}",
+                    e.time,
+                    e.old_status,
+                    e.new_status,
+                    e.actor
+                        .map(|w| w.to_string())
+                        .unwrap_or_else(|| "lurk-chan".to_string()),
+                    e.note.as_deref().unwrap_or("")
+                );
+            }
+        }
+        Commands::Audit { id } => {
+            let action = lc
+                .db
+                .get_action_from_id(id)
+                .await?
+                .context("No such audit")?;
+            log_action(id, &action);
+            if let Some(source) = lc.db.action_import_source(id).await? {
+                info!("  Imported from {}", source);
+            }
+        }
+        Commands::Player { id, guild } => {
+            let guilds = match guild {
+                Some(g) => vec![g],
+                None => lc
+                    .db
+                    .all_guild_settings()
+                    .await?
+                    .into_iter()
+                    .map(|g| g.guild_id)
+                    .collect(),
+            };
+            for guild in guilds {
+                let info = lc.db.collect_user_info(&id, guild).await?;
+                info!(
+                    "In {}: reported {} times, reported others {} times, audited {} times",
+                    guild, info.times_reported, info.times_reported_others, info.times_actioned
+                );
+                for (rid, r) in &info.preview_reported {
+                    log_report(*rid, r);
+                }
+                for (rid, r) in &info.preview_reported_others {
+                    log_report(*rid, r);
+                }
+                for (aid, a) in &info.preview_actioned {
+                    log_action(*aid, a);
+                }
+            }
+        }
+        Commands::Expire { id: None } => {
+            info!("Running the expiry pass");
+            expire(ctx, lc).await?;
+        }
+        Commands::Expire { id: Some(id) } => {
+            let outcome = lc.db.expire_report(id).await?;
+            changed(ctx, lc, id, outcome, "Expired").await?;
+        }
+        Commands::Close { id } => {
+            let outcome = lc
+                .db
+                .close_report(id, me, None, Some("Closed from the console"))
+                .await?;
+            changed(ctx, lc, id, outcome, "Closed").await?;
+        }
+        Commands::Reopen { id } => {
+            let outcome = lc.db.reopen_report(id, me).await?;
+            changed(ctx, lc, id, outcome, "Reopened").await?;
+        }
+        Commands::Backup => {
+            let path = crate::backups::create(lc).await?;
+            info!("Backed up to {}", path.display());
+        }
+        Commands::Render { what, id } => {
+            match what {
+                Render::Report => update_report_message(ctx, id, &lc.db).await?,
+                Render::Audit => update_audit_message(ctx, id, &lc.db).await?,
+            }
+            info!("Updated the message for {:?} #{}", what, id);
+        }
+        Commands::Integrity => {
+            lc.db.integrety_check().await?;
+            match lc.db.foreign_key_check().await? {
+                0 => info!("Database is OK"),
+                n => warn!("{} row(s) point at things that don't exist", n),
+            }
+        }
+        Commands::Quarantine { release: Some(id) } => {
+            if lc.db.release_quarantine(id).await? {
+                info!("Released report #{}", id);
+            } else {
+                warn!("Report #{} wasn't quarantined", id);
+            }
+        }
+        Commands::Quarantine { release: None } => {
+            let q = lc.db.quarantined_reports().await?;
+            if q.is_empty() {
+                info!("Nothing is quarantined");
+            }
+            for (id, reason, at) in q {
+                info!("Report #{} since {}: {}", id, at, reason);
+            }
+        }
+        Commands::Log { level } => {
+            lc.log.modify(|l| *l = level)?;
+            info!("Logging at {} now", level);
+        }
+        Commands::Export(args) => {
+            let path = args.out.clone().unwrap_or_else(|| args.default_path());
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            let mut out = std::io::BufWriter::new(std::fs::File::create(&path)?);
+            let count = args.export(&lc.db, &mut out).await?;
+            out.flush()?;
+            info!("Wrote {} rows to {}", count, path.display());
+        }
+    }
+    Ok(())
+}
+
+/// say how changing a report went, and fix its message if it did
+async fn changed(
+    ctx: &impl CacheHttp,
+    lc: &LurkChan,
+    id: u32,
+    outcome: Outcome,
+    done: &str,
+) -> anyhow::Result<()> {
+    match outcome {
+        Outcome::Done => {
+            update_report_message(ctx, id, &lc.db).await?;
+            info!("{} report #{}", done, id);
+        }
+        Outcome::WrongStatus(s) => warn!("Report #{} is {:?}, not doing that", id, s),
+        o => warn!("Didn't change report #{}: {:?}", id, o),
+    }
+    Ok(())
+}
+
+fn log_report(id: u32, r: &Report) {
+    info!(
+        "Report #{} ({:?}): {} ({}) reported {} ({}) for {:?} on {} at {}, claimed by {}",
+        id,
+        r.report_status,
+        r.reporter_name,
+        r.reporter_id,
+        r.reported_name,
+        r.reported_id,
+        r.report_reason,
+        r.server,
+        r.time,
+        r.claimant
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "nobody".to_string())
+    );
+}
+
+fn log_action(id: u32, a: &Action) {
+    info!(
+        "Audit #{}: {} ({}) for {:?}, action {:?} ({}) by {}{}",
+        id,
+        a.target_username,
+        a.target_id,
+        a.offense,
+        a.action,
+        a.punishment
+            .map_or_else(|| "other".to_string(), |p| p.to_string()),
+        a.claimant,
+        a.report
+            .map(|r| format!(", report #{}", r))
+            .unwrap_or_default()
+    );
 }
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use async_shutdown::ShutdownManager;
+use chrono::{DateTime, Duration, Utc};
+use common::{Location, Report, ReportStatus};
+use database::Outcome;
+use lurk_chan::{db_time, update_report_message};
+use poise::serenity_prelude::{CacheHttp, ChannelId, CreateMessage, MessageId};
+use tokio::select;
+use tracing::{error, info, instrument, warn};
+
+use super::interval;
+use crate::{settings::Settings, ExpiryConfig, LurkChan};
+
+#[instrument(skip(ctx, lc, shut))]
+pub async fn expire_task(
+    ctx: impl CacheHttp,
+    lc: LurkChan,
+    shut: ShutdownManager<&'static str>,
+) -> anyhow::Result<()> {
+    let mut interval = interval(&lc.config.get(), "expire");
+    loop {
+        select! {
+            _ = interval.tick() => {  },
+            _ = shut.wait_shutdown_triggered() => {
+                break;
+            }
+        }
+        expire(&ctx, &lc).await?;
+        lc.tasks.ran("expire");
+    }
+
+    Ok(())
+}
+
+/// release idle claims, then warn about and expire old reports, in every guild
+pub async fn expire(ctx: &impl CacheHttp, lc: &LurkChan) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let config = lc.config.get();
+    // settings can change at any time, so look again every time
+    let mut settings = HashMap::new();
+    for g in lc.db.all_guild_settings().await? {
+        settings.insert(g.guild_id, Settings::load(lc, g.guild_id).await?);
+    }
+    let defaults = Settings::defaults(&config);
+    for (guild, s) in &settings {
+        let Some(hours) = s.claim_idle_hours else {
+            continue;
+        };
+        let before = db_time(now - Duration::hours(hours as i64));
+        for report in lc.db.idle_claimed_reports(*guild, &before).await? {
+            info!("Releasing idle claim on report #{}", report);
+            match lc
+                .db
+                .unclaim_report(
+                    report,
+                    None,
+                    None,
+                    Some("Claim released after sitting idle"),
+                )
+                .await
+            {
+                Ok(Outcome::Done) => {}
+                // someone did something with it since we looked
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("Failed to release report #{}: {}", report, e);
+                    continue;
+                }
+            }
+            if let Err(e) = update_report_message(ctx, report, &lc.db).await {
+                warn!("Failed to update report message #{}: {}", report, e);
+            }
+        }
+    }
+
+    let warned = lc.db.expiry_warnings().await?;
+    let warn_before = config.expiry.warn_hours.map(|h| Duration::hours(h as i64));
+    let mut to_expire = vec![];
+    let mut statuses = vec![ReportStatus::Open];
+    if config.expiry.claimed_hours.is_some() {
+        statuses.push(ReportStatus::Claimed);
+    }
+    for status in statuses {
+        for (id, report) in lc.db.reports_to_expire(status.clone()).await? {
+            let report = match report {
+                Ok(r) => r,
+                Err(e) => {
+                    quarantine(lc, id, format!("it can't be read: {}", e)).await;
+                    continue;
+                }
+            };
+            let hours = match status {
+                ReportStatus::Claimed => config.expiry.claimed_hours.expect("checked above"),
+                _ => {
+                    let guild_hours = report
+                        .guild_id
+                        .and_then(|g| settings.get(&g))
+                        .unwrap_or(&defaults)
+                        .report_expiry_hours;
+                    open_hours(&config.expiry, &report, guild_hours)
+                }
+            };
+            let hours = Duration::hours(hours as i64);
+            let created: DateTime<Utc> = match report.time.parse() {
+                Ok(t) => t,
+                Err(e) => {
+                    quarantine(lc, id, format!("its time {:?} is bad: {}", report.time, e)).await;
+                    continue;
+                }
+            };
+            // it can only have changed since it came in, so it's not even close yet
+            if now < created + hours - warn_before.unwrap_or_default() {
+                continue;
+            }
+            // reports that were claimed, unclaimed or reopened get their time again
+            let since = match lc.db.report_events(id).await {
+                Ok(events) => events
+                    .last()
+                    .and_then(|e| e.time.parse::<DateTime<Utc>>().ok())
+                    .map_or(created, |t| created.max(t)),
+                Err(e) => {
+                    warn!("Failed to get the timeline of report #{}: {}", id, e);
+                    continue;
+                }
+            };
+            let expires = since + hours;
+            if now > expires {
+                to_expire.push((id, status.clone()));
+                continue;
+            }
+            // only open reports need someone to grab them
+            let Some(warn_before) = warn_before.filter(|_| status == ReportStatus::Open) else {
+                continue;
+            };
+            let since = db_time(since);
+            if now + warn_before < expires || warned.get(&id) == Some(&since) {
+                continue;
+            }
+            match warn_expiring(ctx, lc, id, expires).await {
+                Ok(()) => lc.db.set_expiry_warning(id, &since).await?,
+                Err(e) => warn!("Failed to warn about report #{} expiring: {}", id, e),
+            }
+        }
+    }
+    if to_expire.is_empty() {
+        return Ok(());
+    }
+    info!("Expiring {} reports", to_expire.len());
+    for (report, status) in to_expire {
+        let outcome = match status {
+            ReportStatus::Claimed => lc.db.expire_stale_claim(report).await,
+            _ => lc.db.expire_report(report).await,
+        };
+        match outcome {
+            Ok(Outcome::Done) => {}
+            // someone did something with it while we weren't looking
+            Ok(_) => continue,
+            Err(e) => {
+                warn!("Failed to close report #{}: {}", report, e);
+                continue;
+            }
+        }
+        if let Err(e) = update_report_message(ctx, report, &lc.db).await {
+            warn!("Failed to update report message #{}: {}", report, e);
+        }
+    }
+    info!("expire complete");
+    Ok(())
+}
+
+/// how many hours an open report gets: whatever its SL server gets, or its location,
+/// or the guild's `report_expiry_hours`
+fn open_hours(expiry: &ExpiryConfig, report: &Report, guild_hours: u64) -> u64 {
+    let (by_server, by_location) = match report.location {
+        Location::SL => (expiry.servers.get(&report.server).copied(), expiry.sl_hours),
+        Location::Discord => (None, expiry.discord_hours),
+    };
+    by_server.or(by_location).unwrap_or(guild_hours)
+}
+
+/// reply to the report's message, so staff can get to it in time
+async fn warn_expiring(
+    ctx: &impl CacheHttp,
+    lc: &LurkChan,
+    id: u32,
+    expires: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let Some((channel, message)) = lc.db.get_report_message(id).await? else {
+        anyhow::bail!("Report message not found!");
+    };
+    let channel = ChannelId::new(channel);
+    channel
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(format!(
+                    "Report #{} expires <t:{}:R>, claim it if it needs looking at!",
+                    id,
+                    expires.timestamp()
+                ))
+                .reference_message((channel, MessageId::new(message))),
+        )
+        .await?;
+    Ok(())
+}
+
+/// log it loudly and leave it out of every expiry pass until someone releases it
+async fn quarantine(lc: &LurkChan, id: u32, reason: String) {
+    error!(
+        "Quarantining report #{}, {}. expiry will leave it alone until `quarantine --release {}` in the console",
+        id, reason, id
+    );
+    if let Err(e) = lc.db.quarantine_report(id, &reason).await {
+        error!("Failed to quarantine report #{}: {}", id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Location, Report};
+
+    use super::open_hours;
+    use crate::ExpiryConfig;
+
+    #[test]
+    fn most_specific_expiry_wins() {
+        let mut expiry: ExpiryConfig = toml::from_str(
+            r#"
+            sl_hours = 24
+            [servers]
+            "Server 1" = 6
+            "#,
+        )
+        .unwrap();
+        let mut report = Report {
+            location: Location::SL,
+            server: "Server 1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(open_hours(&expiry, &report, 48), 6);
+        report.server = "Server 2".to_string();
+        assert_eq!(open_hours(&expiry, &report, 48), 24);
+        report.location = Location::Discord;
+        assert_eq!(open_hours(&expiry, &report, 48), 48);
+        expiry.discord_hours = Some(12);
+        assert_eq!(open_hours(&expiry, &report, 48), 12);
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Context as _;
+use async_shutdown::ShutdownManager;
+use database::{OutboxItem, SideEffect};
+use lurk_chan::{
+    create_action_components, create_action_embed, update_audit_message, update_report_message,
+};
+use poise::serenity_prelude::{self as serenity, CacheHttp, ChannelId, CreateMessage, MessageId};
+use tokio::select;
+use tracing::{error, info, instrument, warn};
+
+use crate::LurkChan;
+
+/// how many outbox items to try at once
+const BATCH: u32 = 20;
+/// never wait longer than this between tries
+const MAX_RETRY_SECS: u64 = 60 * 60;
+
+/// keeps retrying discord side effects that didn't work the first time
+#[instrument(skip(ctx, lc, shut))]
+pub async fn outbox_task(
+    ctx: impl CacheHttp,
+    lc: LurkChan,
+    shut: ShutdownManager<&'static str>,
+) -> anyhow::Result<()> {
+    let mut interval = super::interval(&lc.config.get(), "outbox");
+    loop {
+        select! {
+            _ = interval.tick() => {},
+            _ = shut.wait_shutdown_triggered() => {
+                break;
+            }
+        }
+        flush_outbox(&ctx, &lc).await;
+        lc.tasks.ran("outbox");
+    }
+    Ok(())
+}
+
+/// try everything in the outbox that's due. anything that fails stays there for later
+pub async fn flush_outbox(ctx: &impl CacheHttp, lc: &LurkChan) {
+    loop {
+        let items = match lc.db.take_due_outbox(BATCH).await {
+            Ok(i) => i,
+            Err(e) => {
+                error!("Failed to read the outbox: {}", e);
+                return;
+            }
+        };
+        if items.is_empty() {
+            return;
+        }
+        let count = items.len();
+        for item in items {
+            match deliver(ctx, lc, &item).await {
+                Ok(posted) => {
+                    if let Err(e) = lc.db.finish_outbox(&item, posted).await {
+                        error!("Failed to finish outbox item #{}: {}", item.id, e);
+                    }
+                }
+                Err(e) => {
+                    let retry_in = (30u64 << item.attempts.min(7)).min(MAX_RETRY_SECS);
+                    warn!(
+                        "{:?} failed (try #{}), retrying in {}s: {}",
+                        item.effect, item.attempts, retry_in, e
+                    );
+                    if let Err(e) = lc.db.fail_outbox(item.id, &e.to_string(), retry_in).await {
+                        error!("Failed to reschedule outbox item #{}: {}", item.id, e);
+                    }
+                }
+            }
+        }
+        if count < BATCH as usize {
+            return;
+        }
+    }
+}
+
+/// do one side effect. for a new audit message, where it ended up
+async fn deliver(
+    ctx: &impl CacheHttp,
+    lc: &LurkChan,
+    item: &OutboxItem,
+) -> anyhow::Result<Option<(u64, u64)>> {
+    match &item.effect {
+        SideEffect::PostAudit(aid) => {
+            let Some(a) = lc.db.get_action_from_id(*aid).await? else {
+                info!("Audit #{} is gone, not posting it", aid);
+                return Ok(None);
+            };
+            let guild = a.guild_id.context("Audit isn't in a guild")?;
+            let channel = lc
+                .db
+                .guild_settings(guild)
+                .await?
+                .and_then(|s| s.audit_channel(&a.server))
+                .map(ChannelId::new)
+                .with_context(|| format!("No {:?} audit channel set up in {}", a.server, guild))?;
+            let m = channel
+                .send_message(
+                    ctx,
+                    CreateMessage::default()
+                        .embed(create_action_embed(&a, ctx, *aid, channel).await?)
+                        .components(create_action_components(*aid)),
+                )
+                .await?;
+            Ok(Some((m.channel_id.get(), m.id.get())))
+        }
+        SideEffect::UpdateReport(rid) => {
+            update_report_message(ctx, *rid, &lc.db).await?;
+            Ok(None)
+        }
+        SideEffect::UpdateAudit(aid) => {
+            update_audit_message(ctx, *aid, &lc.db).await?;
+            Ok(None)
+        }
+        SideEffect::DeleteMessage { channel, message } => {
+            match ChannelId::new(*channel)
+                .delete_message(ctx.http(), MessageId::new(*message))
+                .await
+            {
+                // someone beat us to it
+                Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(r)))
+                    if r.status_code.as_u16() == 404 =>
+                {
+                    Ok(None)
+                }
+                r => r.map(|_| None).map_err(Into::into),
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use async_shutdown::ShutdownManager;
+use chrono::{DateTime, Duration, Utc};
+use database::ActivePunishment;
+use lurk_chan::db_time;
+use poise::serenity_prelude::{
+    CacheHttp, ChannelId, Color, CreateEmbed, CreateEmbedFooter, CreateMessage, MessageId,
+    MessageReference,
+};
+use tokio::select;
+use tracing::{debug, info, instrument, warn};
+
+use super::interval;
+use crate::LurkChan;
+
+/// punishments that ran out longer ago than this aren't announced. stops years of imported bans
+/// (or a long time offline) from flooding the audit channels
+const NOTICE_WINDOW_HOURS: i64 = 24;
+
+#[instrument(skip(ctx, lc, shut))]
+pub async fn punishments_task(
+    ctx: impl CacheHttp,
+    lc: LurkChan,
+    shut: ShutdownManager<&'static str>,
+) -> anyhow::Result<()> {
+    let mut interval = interval(&lc.config.get(), "punishments");
+    loop {
+        select! {
+            _ = interval.tick() => {  },
+            _ = shut.wait_shutdown_triggered() => {
+                break;
+            }
+        }
+        announce_ended(&ctx, &lc).await?;
+        lc.tasks.ran("punishments");
+    }
+    Ok(())
+}
+
+/// tell each audit channel about bans and timeouts that just ran out
+async fn announce_ended(ctx: &impl CacheHttp, lc: &LurkChan) -> anyhow::Result<()> {
+    let since = db_time(Utc::now() - Duration::hours(NOTICE_WINDOW_HOURS));
+    let ended = lc.db.ended_punishments(&since).await?;
+    if ended.is_empty() {
+        return Ok(());
+    }
+    info!("{} punishment(s) ran out", ended.len());
+    for p in ended {
+        match announce(ctx, lc, &p).await {
+            Ok(message) => lc.db.set_punishment_noticed(p.id, message).await?,
+            Err(e) => warn!("Failed to announce audit #{} running out: {}", p.id, e),
+        }
+    }
+    Ok(())
+}
+
+/// the (channel, message) it was announced in, if there was anywhere to
+async fn announce(
+    ctx: &impl CacheHttp,
+    lc: &LurkChan,
+    p: &ActivePunishment,
+) -> anyhow::Result<Option<(u64, u64)>> {
+    let a = &p.action;
+    let channel = match a.guild_id {
+        Some(g) => lc
+            .db
+            .guild_settings(g)
+            .await?
+            .and_then(|s| s.audit_channel(&a.server)),
+        None => None,
+    };
+    let Some(channel) = channel.map(ChannelId::new) else {
+        // nowhere to say it, so there's nothing to retry
+        debug!("No audit channel for audit #{}, not announcing it", p.id);
+        return Ok(None);
+    };
+    let audits = lc.db.get_action_count(&a.target_id, a.guild_id).await?;
+    let mut embed = CreateEmbed::new()
+        .title(format!(
+            "{} over",
+            a.punishment
+                .map_or_else(|| "Punishment".to_string(), |p| p.to_string())
+        ))
+        .color(Color::DARK_GREEN)
+        .field("ID", or_unknown(&a.target_id), false)
+        .field("Username", or_unknown(&a.target_username), false)
+        .field("Offense", or_unknown(&a.offense), false)
+        .field("Audits on record", audits.to_string(), false)
+        .footer(CreateEmbedFooter::new(format!("Audit Log #{}", p.id)));
+    if let Some(t) = p
+        .expires_at
+        .as_deref()
+        .and_then(|t| t.parse::<DateTime<Utc>>().ok())
+    {
+        embed = embed.timestamp(t);
+    }
+    let mut msg = CreateMessage::new().embed(embed);
+    // reply to the audit, if it's in the same channel
+    if let Some((c, m)) = lc.db.get_action_message(p.id).await? {
+        if ChannelId::new(c) == channel {
+            msg = msg.reference_message(
+                MessageReference::from((channel, MessageId::new(m))).fail_if_not_exists(false),
+            );
+        }
+    }
+    let sent = channel.send_message(ctx, msg).await?;
+    Ok(Some((channel.get(), sent.id.get())))
+}
+
+/// discord won't take an empty field, and SL's ban files don't always have a reason
+fn or_unknown(s: &str) -> String {
+    if s.trim().is_empty() {
+        "???".to_string()
+    } else {
+        s.to_string()
+    }
+}
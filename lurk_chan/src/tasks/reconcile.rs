@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use async_shutdown::ShutdownManager;
+use lurk_chan::{create_action_components, create_action_embed, create_things_from_report};
+use poise::serenity_prelude::{
+    self as serenity,
+    futures::{StreamExt as _, TryStreamExt as _},
+    CacheHttp, ChannelId, CreateMessage, MessageId,
+};
+use tokio::select;
+use tracing::{info, instrument, warn};
+
+use crate::LurkChan;
+
+/// read at most this many messages per channel, newest first. tracked messages older than that
+/// aren't checked, so years of history don't get read every run
+const SCAN_LIMIT: usize = 2000;
+
+/// what a reconcile run found
+#[derive(Debug, Default)]
+pub struct Reconciled {
+    /// tracked messages that were checked
+    pub checked: usize,
+    /// tracked messages that were gone and got posted again
+    pub reposted: usize,
+    /// tracked messages that couldn't be checked or posted again
+    pub failed: usize,
+    /// tracked messages older than the last [`SCAN_LIMIT`] messages in their channel
+    pub unchecked: usize,
+    /// our messages the database doesn't know about, as (channel, message, title)
+    pub orphans: Vec<(ChannelId, MessageId, String)>,
+}
+
+/// a message we know about
+#[derive(Clone, Copy)]
+enum Tracked {
+    Report(u32),
+    Audit(u32),
+}
+
+#[instrument(skip(ctx, lc, shut))]
+pub async fn reconcile_task(
+    ctx: impl CacheHttp,
+    lc: LurkChan,
+    shut: ShutdownManager<&'static str>,
+) -> anyhow::Result<()> {
+    // give discord (and the catch up) time to get going
+    select! {
+        _ = tokio::time::sleep(Duration::from_secs(60)) => {},
+        _ = shut.wait_shutdown_triggered() => {
+            return Ok(());
+        }
+    }
+    let mut interval = super::interval(&lc.config.get(), "reconcile");
+    loop {
+        select! {
+            _ = interval.tick() => {},
+            _ = shut.wait_shutdown_triggered() => {
+                break;
+            }
+        }
+        let r = reconcile(&ctx, &lc)
+            .await
+            .context("Failed to reconcile messages")?;
+        log_reconciled(&r);
+        lc.tasks.ran("reconcile");
+    }
+    Ok(())
+}
+
+pub fn log_reconciled(r: &Reconciled) {
+    info!(
+        "Reconciled {} messages: {} posted again, {} failed, {} too old to check, {} orphans",
+        r.checked,
+        r.reposted,
+        r.failed,
+        r.unchecked,
+        r.orphans.len()
+    );
+    for (channel, message, title) in &r.orphans {
+        warn!(
+            "Orphan message {} in channel {} ({})",
+            message, channel, title
+        );
+    }
+}
+
+/// check the recent report and audit messages we know about, post the missing ones again,
+/// and find messages of ours we don't know about. each channel is only read back to the oldest
+/// message we track in it, and never more than [`SCAN_LIMIT`] messages
+pub async fn reconcile(ctx: &impl CacheHttp, lc: &LurkChan) -> anyhow::Result<Reconciled> {
+    let me = ctx.cache().context("No cache")?.current_user().id;
+    let mut by_channel: BTreeMap<ChannelId, Vec<(MessageId, Tracked)>> = BTreeMap::new();
+    for (rid, channel, message) in lc.db.all_report_messages().await? {
+        by_channel
+            .entry(ChannelId::new(channel))
+            .or_default()
+            .push((MessageId::new(message), Tracked::Report(rid)));
+    }
+    for (aid, channel, message) in lc.db.all_action_messages().await? {
+        by_channel
+            .entry(ChannelId::new(channel))
+            .or_default()
+            .push((MessageId::new(message), Tracked::Audit(aid)));
+    }
+    let mut known: HashSet<MessageId> = by_channel
+        .values()
+        .flat_map(|v| v.iter().map(|(m, _)| *m))
+        .collect();
+    // punishments running out are ours too, but they don't get posted again
+    known.extend(
+        lc.db
+            .all_notice_messages()
+            .await?
+            .into_iter()
+            .map(|(_, m)| MessageId::new(m)),
+    );
+
+    let mut r = Reconciled::default();
+    'channels: for (channel, tracked) in by_channel {
+        let oldest = tracked.iter().map(|(m, _)| *m).min();
+        // everything we posted in there that's still around
+        let mut ours = HashSet::new();
+        // the oldest message we read, if we stopped at the limit before reaching the end
+        let mut reached = None;
+        let mut capped = false;
+        let mut read = 0;
+        let mut messages = channel.messages_iter(ctx.http()).boxed();
+        loop {
+            if read == SCAN_LIMIT {
+                capped = true;
+                break;
+            }
+            read += 1;
+            let m = match messages.try_next().await {
+                Ok(Some(m)) => m,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Failed to read channel {}, skipping it: {}", channel, e);
+                    r.failed += tracked.len();
+                    continue 'channels;
+                }
+            };
+            reached = Some(m.id);
+            if oldest.is_some_and(|o| m.id < o) {
+                break;
+            }
+            if m.author.id != me {
+                continue;
+            }
+            ours.insert(m.id);
+            if !known.contains(&m.id) && !m.embeds.is_empty() {
+                let title = m.embeds[0].title.clone().unwrap_or_default();
+                r.orphans.push((channel, m.id, title));
+            }
+        }
+        for (message, t) in tracked {
+            if ours.contains(&message) {
+                r.checked += 1;
+                continue;
+            }
+            if capped && reached.is_some_and(|m| message < m) {
+                r.unchecked += 1;
+                continue;
+            }
+            r.checked += 1;
+            match is_gone(ctx, channel, message).await {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(e) => {
+                    warn!("Failed to check message {}: {}", message, e);
+                    r.failed += 1;
+                    continue;
+                }
+            }
+            match repost(ctx, lc, channel, t).await {
+                Ok(()) => r.reposted += 1,
+                Err(e) => {
+                    warn!("Failed to post message {} again: {}", message, e);
+                    r.failed += 1;
+                }
+            }
+        }
+    }
+    Ok(r)
+}
+
+/// double check, it might have been posted after we looked
+async fn is_gone(
+    ctx: &impl CacheHttp,
+    channel: ChannelId,
+    message: MessageId,
+) -> anyhow::Result<bool> {
+    match channel.message(ctx, message).await {
+        Ok(_) => Ok(false),
+        Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(r)))
+            if r.status_code.as_u16() == 404 =>
+        {
+            Ok(true)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn repost(
+    ctx: &impl CacheHttp,
+    lc: &LurkChan,
+    channel: ChannelId,
+    t: Tracked,
+) -> anyhow::Result<()> {
+    match t {
+        Tracked::Report(rid) => {
+            let report = lc
+                .db
+                .get_report_from_id(rid)
+                .await?
+                .context("Report not found!")?;
+            let (embed, comp) = create_things_from_report(report, rid, &lc.db).await?;
+            let m = channel
+                .send_message(ctx, CreateMessage::default().embed(embed).components(comp))
+                .await?;
+            lc.db
+                .add_report_message(m.channel_id.get(), m.id.get(), rid)
+                .await?;
+            info!("Posted report #{} again", rid);
+        }
+        Tracked::Audit(aid) => {
+            let action = lc
+                .db
+                .get_action_from_id(aid)
+                .await?
+                .context("Action not found!")?;
+            let m = channel
+                .send_message(
+                    ctx,
+                    CreateMessage::default()
+                        .embed(create_action_embed(&action, ctx, aid, channel).await?)
+                        .components(create_action_components(aid)),
+                )
+                .await?;
+            lc.db
+                .add_action_message(m.channel_id.get(), m.id.get(), aid)
+                .await?;
+            info!("Posted audit #{} again", aid);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::time::SystemTime;
+
+use async_shutdown::ShutdownManager;
+use poise::serenity_prelude::CacheHttp;
+use tokio::select;
+use tracing::{error, info, instrument};
+
+use crate::{LurkChan, CONFIG_PATH};
+
+/// reloads the config when the file changes, or on SIGHUP
+#[instrument(skip(_ctx, lc, shut))]
+pub async fn reload_task(
+    _ctx: impl CacheHttp,
+    lc: LurkChan,
+    shut: ShutdownManager<&'static str>,
+) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    let mut interval = super::interval(&lc.config.get(), "reload");
+    let mut last_modified = modified().await;
+    loop {
+        #[cfg(unix)]
+        let hungup = hangup.recv();
+        #[cfg(not(unix))]
+        let hungup = std::future::pending::<Option<()>>();
+        select! {
+            _ = interval.tick() => {
+                lc.tasks.ran("reload");
+                let m = modified().await;
+                if m == last_modified {
+                    continue;
+                }
+                last_modified = m;
+                reload(&lc, "file changed");
+            },
+            _ = hungup => reload(&lc, "SIGHUP"),
+            _ = shut.wait_shutdown_triggered() => {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn modified() -> Option<SystemTime> {
+    tokio::fs::metadata(CONFIG_PATH).await.ok()?.modified().ok()
+}
+
+/// reload the config, saying how it went. a bad config is logged and ignored
+pub fn reload(lc: &LurkChan, why: &str) {
+    match lc.config.reload() {
+        Ok(()) => info!("Reloaded the config ({})", why),
+        Err(e) => error!(
+            "Not reloading the config ({}), still using the old one: {:#}",
+            why, e
+        ),
+    }
+}
@@ -1,16 +1,21 @@
+use std::collections::{hash_map::Entry, HashMap};
 use std::time::Duration;
 
 use anyhow::Context as _;
 use async_shutdown::ShutdownManager;
-use common::{Location, ReportStatus};
+use chrono::{DateTime, Utc};
+use common::{Location, Punishment, ReportStatus};
+use database::ResponseTimes;
+use lurk_chan::{db_time, format_duration};
 use poise::serenity_prelude::{
     futures::{StreamExt as _, TryStreamExt as _},
-    CacheHttp, Color, CreateEmbed, CreateMessage, EditMessage, Timestamp,
+    CacheHttp, ChannelId, Color, CreateEmbed, CreateMessage, EditMessage, Message, Timestamp,
 };
 use tokio::{select, try_join};
 use tracing::{error, info, instrument};
 
-use crate::LurkChan;
+use super::State;
+use crate::{settings::Settings, LurkChan};
 
 #[instrument(skip(ctx, lc, shut))]
 pub async fn stats_task(
@@ -20,31 +25,10 @@ pub async fn stats_task(
 ) -> anyhow::Result<()> {
     info!("ayy!");
     tokio::time::sleep(Duration::from_secs(10)).await; // give discord time to get going
-    let mut s = lc.config.discord.stats.messages_iter(ctx.http()).boxed();
-    let mut m = None;
-    while let Ok(Some(e)) = s.try_next().await {
-        if e.author == **ctx.cache().expect("has cache").current_user() {
-            m.replace(e);
-            break;
-        }
-    }
-    let mut msg = match m {
-        Some(m) => m,
-        None => lc
-            .config
-            .discord
-            .stats
-            .send_message(
-                &ctx,
-                CreateMessage::new().content("Loading first time stats..."),
-            )
-            .await
-            .context("Failed to send new message")?,
-    };
-    const TIME: u64 = 30;
-    let mut interval = tokio::time::interval(Duration::from_secs(TIME));
-    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
-    let mut uptime = chrono::Duration::seconds(-(TIME as i64));
+
+    // the stats message in each stats channel
+    let mut messages: HashMap<ChannelId, Message> = HashMap::new();
+    let mut interval = super::interval(&lc.config.get(), "stats");
     loop {
         select! {
             _ = interval.tick() => {  },
@@ -52,60 +36,110 @@ pub async fn stats_task(
                 break;
             }
         }
-        uptime = uptime + chrono::Duration::seconds(TIME as i64);
+        let uptime = Utc::now() - lc.tasks.started_at;
 
-        let embeds = try_join!(
-            changelog_embed(uptime.clone()),
-            db_data_embed(&lc),
-            detailed_stats_embed(&lc),
-            leaderboard_embed(&lc)
-        )?;
-
-        let mut emb = vec![embeds.0, embeds.1, embeds.2, embeds.3];
-        let new_last = emb.pop().expect("fuck").timestamp(Timestamp::now());
-        emb.push(new_last);
-        msg.edit(&ctx, EditMessage::new().content("Stats:").embeds(emb))
-            .await?;
+        let (changelog, db_data) = try_join!(changelog_embed(uptime), db_data_embed(&lc))?;
+        for settings in lc.db.all_guild_settings().await? {
+            let Some(channel) = settings.stats.map(ChannelId::new) else {
+                continue;
+            };
+            let guild = settings.guild_id;
+            let size = Settings::load(&lc, guild).await?.leaderboard_size;
+            let embeds = try_join!(
+                detailed_stats_embed(&lc, guild),
+                leaderboard_embed(&lc, guild, size, "Leaderboard", None),
+                leaderboard_embed(
+                    &lc,
+                    guild,
+                    size,
+                    "Weekly Leaderboard",
+                    Some(chrono::Utc::now() - chrono::Duration::days(7))
+                )
+            )?;
+            let emb = vec![
+                changelog.clone(),
+                db_data.clone(),
+                tasks_embed(&lc),
+                embeds.0,
+                embeds.1,
+                embeds.2.timestamp(Timestamp::now()),
+            ];
+            let msg = match messages.entry(channel) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => match stats_message(&ctx, channel).await {
+                    Ok(m) => e.insert(m),
+                    Err(err) => {
+                        error!("Failed to find a stats message in {}: {}", channel, err);
+                        continue;
+                    }
+                },
+            };
+            if let Err(e) = msg
+                .edit(&ctx, EditMessage::new().content("Stats:").embeds(emb))
+                .await
+            {
+                error!("Failed to update stats in {}: {}", channel, e);
+                // find it again next time, it might have been deleted
+                messages.remove(&channel);
+            }
+        }
+        lc.tasks.ran("stats");
     }
     Ok(())
 }
 
+/// our last message in `channel`, or a new one
+async fn stats_message(ctx: &impl CacheHttp, channel: ChannelId) -> anyhow::Result<Message> {
+    let me = ctx.cache().context("No cache")?.current_user().id;
+    let mut s = channel.messages_iter(ctx.http()).boxed();
+    while let Ok(Some(e)) = s.try_next().await {
+        if e.author.id == me {
+            return Ok(e);
+        }
+    }
+    channel
+        .send_message(
+            ctx,
+            CreateMessage::new().content("Loading first time stats..."),
+        )
+        .await
+        .context("Failed to send new message")
+}
+
 use ordinal::Ordinal;
-pub async fn leaderboard_embed(lc: &LurkChan) -> anyhow::Result<CreateEmbed> {
+pub async fn leaderboard_embed(
+    lc: &LurkChan,
+    guild: u64,
+    size: u32,
+    title: &str,
+    since: Option<DateTime<Utc>>,
+) -> anyhow::Result<CreateEmbed> {
     use std::fmt::Write;
-    const LEADERBOARD_LIMIT: u32 = 15;
-    let (reports_leader, audit_leader) = try_join!(
-        lc.db.leaderboard_reports(LEADERBOARD_LIMIT),
-        lc.db.leaderboard_audit(LEADERBOARD_LIMIT)
+    let since = since.map(db_time);
+    let ((_, reports_leader), (_, audit_leader)) = try_join!(
+        lc.db
+            .leaderboard_reports_between(guild, since.as_deref(), None, size, 0),
+        lc.db
+            .leaderboard_audit_between(guild, since.as_deref(), None, size, 0)
     )?;
+    let board = |leader: Vec<(u64, u32)>| {
+        let b = leader
+            .into_iter()
+            .enumerate()
+            .fold(String::new(), |mut o, (place, r)| {
+                let _ = writeln!(o, "* {}. <@!{}> - {}", Ordinal(place + 1), r.0, r.1);
+                o
+            });
+        if b.is_empty() {
+            "Nobody yet!".to_string()
+        } else {
+            b
+        }
+    };
     Ok(CreateEmbed::new()
-        .title("Leaderboard")
-        .field(
-            "Reports",
-            {
-                reports_leader
-                    .into_iter()
-                    .enumerate()
-                    .fold(String::new(), |mut o, (place, r)| {
-                        let _ = writeln!(o, "* {}. <@!{}> - {}", Ordinal(place + 1), r.0, r.1);
-                        o
-                    })
-            },
-            false,
-        )
-        .field(
-            "Audits",
-            {
-                audit_leader
-                    .into_iter()
-                    .enumerate()
-                    .fold(String::new(), |mut o, (place, r)| {
-                        let _ = writeln!(o, "* {}. <@!{}> - {}", Ordinal(place + 1), r.0, r.1);
-                        o
-                    })
-            },
-            false,
-        )
+        .title(title)
+        .field("Reports", board(reports_leader), false)
+        .field("Audits", board(audit_leader), false)
         .color(Color::BLURPLE))
 }
 
@@ -178,7 +212,38 @@ pub async fn db_data_embed(lc: &LurkChan) -> anyhow::Result<CreateEmbed> {
     })
 }
 
-pub async fn detailed_stats_embed(lc: &LurkChan) -> anyhow::Result<CreateEmbed> {
+/// how the background tasks are doing
+pub fn tasks_embed(lc: &LurkChan) -> CreateEmbed {
+    use std::fmt::Write;
+    let health = lc.tasks.health();
+    let healthy = health
+        .iter()
+        .all(|(_, h)| !matches!(h.state, State::Restarting(_)));
+    let description = health.iter().fold(String::new(), |mut o, (name, h)| {
+        let mut summary = h.summary();
+        // a long error could push the whole embed over discord's limit
+        if summary.len() > 300 {
+            summary = format!("{}...", summary.chars().take(300).collect::<String>());
+        }
+        writeln!(o, "**{}**: {}", name, summary).unwrap();
+        o
+    });
+    CreateEmbed::new()
+        .title(format!(
+            "Tasks: {}",
+            if healthy { "Healthy" } else { "Restarting" }
+        ))
+        .description(description)
+        .color(if healthy {
+            Color::from_rgb(0, 255, 0)
+        } else {
+            Color::from_rgb(255, 0, 0)
+        })
+}
+
+pub async fn detailed_stats_embed(lc: &LurkChan, guild: u64) -> anyhow::Result<CreateEmbed> {
+    use std::fmt::Write;
+    const STAFF_TIMES_LIMIT: usize = 10;
     let (
         open_reports,
         claimed_reports,
@@ -189,23 +254,79 @@ pub async fn detailed_stats_embed(lc: &LurkChan) -> anyhow::Result<CreateEmbed>
         sl_reports,
         disc_reports,
         reports_no_audit,
+        location_times,
+        staff_times,
+        punishments,
     ) = try_join!(
-        lc.db.get_report_count_by_status(ReportStatus::Open),
-        lc.db.get_report_count_by_status(ReportStatus::Claimed),
+        lc.db.get_report_count_by_status(ReportStatus::Open, guild),
+        lc.db
+            .get_report_count_by_status(ReportStatus::Claimed, guild),
         async {
             try_join!(
-                lc.db.get_report_count_by_status(ReportStatus::Closed),
-                lc.db.get_report_count_by_status(ReportStatus::Expired)
+                lc.db
+                    .get_report_count_by_status(ReportStatus::Closed, guild),
+                lc.db
+                    .get_report_count_by_status(ReportStatus::Expired, guild)
             )
             .map(|i| i.0 + i.1)
         },
-        lc.db.audit_count_from_server(Location::SL),
-        lc.db.audit_count_from_server(Location::Discord),
-        lc.db.audit_count_without_report(),
-        lc.db.report_count_from_server(Location::SL),
-        lc.db.report_count_from_server(Location::Discord),
-        lc.db.report_count_no_audit()
+        lc.db.audit_count_from_server(Location::SL, guild),
+        lc.db.audit_count_from_server(Location::Discord, guild),
+        lc.db.audit_count_without_report(guild),
+        lc.db.report_count_from_server(Location::SL, guild),
+        lc.db.report_count_from_server(Location::Discord, guild),
+        lc.db.report_count_no_audit(guild),
+        lc.db.response_times_by_location(guild),
+        lc.db.response_times_by_staff(guild),
+        lc.db.punishment_counts(Some(guild))
     )?;
+    let location_times = location_times
+        .into_iter()
+        .fold(String::new(), |mut o, (location, t)| {
+            let _ = writeln!(
+                o,
+                "* {}: claimed in {}, closed in {}",
+                match location {
+                    Location::SL => "SL",
+                    Location::Discord => "Discord",
+                },
+                format_times(&t.to_claim),
+                format_times(&t.to_close)
+            );
+            o
+        });
+    let staff_times =
+        staff_times
+            .into_iter()
+            .take(STAFF_TIMES_LIMIT)
+            .fold(String::new(), |mut o, (staff, t)| {
+                let _ = writeln!(
+                    o,
+                    "* <@!{}>: claimed in {}, closed in {}",
+                    staff,
+                    format_times(&t.to_claim),
+                    format_times(&t.to_close)
+                );
+                o
+            });
+    let or_nothing = |s: String| {
+        if s.is_empty() {
+            "Nothing yet".to_string()
+        } else {
+            s
+        }
+    };
+    let punishments = punishments
+        .into_iter()
+        .fold(String::new(), |mut o, (kind, count)| {
+            let _ = writeln!(o, "* {}: {}", Punishment::kind_name(&kind), count);
+            o
+        });
+    let (location_times, staff_times, punishments) = (
+        or_nothing(location_times),
+        or_nothing(staff_times),
+        or_nothing(punishments),
+    );
 
     let detailed_stats_embed = CreateEmbed::new()
         .title("Detailed Stats")
@@ -222,20 +343,38 @@ pub async fn detailed_stats_embed(lc: &LurkChan) -> anyhow::Result<CreateEmbed>
             "Audits Without Report",
             audits_without_report.to_string(),
             true,
-        );
+        )
+        .field("Punishments", punishments, false)
+        .field("Response Times", location_times, false)
+        .field("Staff Response Times", staff_times, false);
     Ok(detailed_stats_embed)
 }
 
+/// `median (avg mean)`, or `n/a` when there is nothing to go off of
+fn format_times(t: &ResponseTimes) -> String {
+    if t.count == 0 {
+        "n/a".to_string()
+    } else {
+        format!(
+            "{} (avg {})",
+            format_duration(t.median),
+            format_duration(t.mean)
+        )
+    }
+}
+
 pub async fn changelog_embed(uptime: chrono::Duration) -> anyhow::Result<CreateEmbed> {
     use std::fmt::Write;
     let raw_change_log = include_str!("../../../changelog.md");
-    let actual_change_log = raw_change_log
-        .lines()
-        .map_while(|l| if l.is_empty() { None } else { Some(l) })
-        .fold(String::new(), |mut o, i| {
-            writeln!(o, "{}", i).unwrap();
-            o
-        });
+    let mut actual_change_log = String::new();
+    for l in raw_change_log.lines().take_while(|l| !l.is_empty()) {
+        // an embed field only fits 1024 characters, with room for the ```md
+        if actual_change_log.len() + l.len() > 1024 - 16 {
+            actual_change_log += "...\n";
+            break;
+        }
+        writeln!(actual_change_log, "{}", l).unwrap();
+    }
 
     Ok(CreateEmbed::new()
         .color(Color::from((0xff, 0x6e, 0xee)))
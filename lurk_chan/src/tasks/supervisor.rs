@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_shutdown::ShutdownManager;
+use chrono::{DateTime, Utc};
+use tokio::select;
+use tracing::{error, info};
+
+use crate::{Config, LurkChan};
+
+/// the first restart waits this long, then twice as long every time after that
+const BASE_BACKOFF: Duration = Duration::from_secs(5);
+/// never wait longer than this to restart a task
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// the tasks that run on a timer, and how often they run (in seconds) unless `[tasks]` says otherwise
+pub const INTERVALS: [(&str, u64); 8] = [
+    ("optimize_db", 60 * 60),
+    ("stats", 30),
+    ("backup", 60 * 60),
+    ("expire", 5 * 60),
+    ("punishments", 5 * 60),
+    ("outbox", 30),
+    ("reconcile", 12 * 60 * 60),
+    ("reload", 5),
+];
+
+/// how a background task is doing
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    /// rounds of work it got through
+    pub runs: u64,
+    pub last_run: Option<DateTime<Utc>>,
+    /// times it died and had to be started again
+    pub restarts: u64,
+    pub last_error: Option<(DateTime<Utc>, String)>,
+    pub state: State,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum State {
+    #[default]
+    Running,
+    /// died, and waiting to be started again
+    Restarting(DateTime<Utc>),
+    /// finished, and won't run again
+    Done,
+}
+
+impl Health {
+    /// one line about it, for the console and the stats channel
+    pub fn summary(&self) -> String {
+        let time = |t: &DateTime<Utc>| t.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+        let mut s = match self.state {
+            State::Running => "running".to_string(),
+            State::Restarting(at) => format!("failed, restarting at {}", time(&at)),
+            State::Done => "done".to_string(),
+        };
+        s += &format!(", {} runs", self.runs);
+        if let Some(t) = &self.last_run {
+            s += &format!(" (last {})", time(t));
+        }
+        if self.restarts > 0 {
+            s += &format!(", {} restarts", self.restarts);
+        }
+        if let Some((t, e)) = &self.last_error {
+            s += &format!(", last error at {}: {}", time(t), e);
+        }
+        s
+    }
+}
+
+/// keeps track of every background task
+#[derive(Clone)]
+pub struct Supervisor {
+    tasks: Arc<Mutex<BTreeMap<&'static str, Health>>>,
+    pub started_at: DateTime<Utc>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self {
+            tasks: Default::default(),
+            started_at: Utc::now(),
+        }
+    }
+}
+
+impl Supervisor {
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut Health)) {
+        f(self
+            .tasks
+            .lock()
+            .expect("not poisoned")
+            .entry(name)
+            .or_default())
+    }
+    /// a task got through a round of work
+    pub fn ran(&self, name: &'static str) {
+        self.update(name, |h| {
+            h.runs += 1;
+            h.last_run = Some(Utc::now());
+        })
+    }
+    /// every task we know about, by name
+    pub fn health(&self) -> Vec<(&'static str, Health)> {
+        self.tasks
+            .lock()
+            .expect("not poisoned")
+            .iter()
+            .map(|(n, h)| (*n, h.clone()))
+            .collect()
+    }
+}
+
+/// how often `name` runs, from `[tasks]` in the config or [`INTERVALS`]
+pub fn interval(config: &Config, name: &str) -> tokio::time::Interval {
+    let secs = config.tasks.get(name).copied().unwrap_or_else(|| {
+        INTERVALS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, s)| *s)
+            .expect("a task with an interval")
+    });
+    let mut interval = tokio::time::interval(Duration::from_secs(secs));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    interval
+}
+
+/// run a task until it's done, starting it again (after a while) whenever it fails
+pub async fn supervise<F, Fut>(
+    name: &'static str,
+    lc: &LurkChan,
+    shut: &ShutdownManager<&'static str>,
+    mut task: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut backoff = BASE_BACKOFF;
+    loop {
+        lc.tasks.update(name, |h| h.state = State::Running);
+        let started = Instant::now();
+        let e = match task().await {
+            Ok(()) => break,
+            Err(e) => e,
+        };
+        // it worked for a good while before dying, so it's not stuck failing
+        if started.elapsed() > MAX_BACKOFF {
+            backoff = BASE_BACKOFF;
+        }
+        if shut.is_shutdown_triggered() {
+            error!("'{}' failed while shutting down: {:#}", name, e);
+            break;
+        }
+        error!(
+            "'{}' failed, starting it again in {}s: {:#}",
+            name,
+            backoff.as_secs(),
+            e
+        );
+        let now = Utc::now();
+        lc.tasks.update(name, |h| {
+            h.restarts += 1;
+            h.last_error = Some((now, format!("{:#}", e)));
+            h.state = State::Restarting(now + backoff);
+        });
+        select! {
+            _ = tokio::time::sleep(backoff) => {},
+            _ = shut.wait_shutdown_triggered() => break,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        info!("Starting '{}' again", name);
+    }
+    lc.tasks.update(name, |h| h.state = State::Done);
+}